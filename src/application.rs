@@ -1,30 +1,124 @@
-use crate::model::{Ability, Map, Nameable, ProgressStore, Usage, Zone};
-use crate::render::Renderable;
-use crate::selection::{Selection, Selector};
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
-use lazy_static::lazy_static;
-use regex::Regex;
+use crate::cli::Change;
+use crate::command_line::{self, Line};
+use crate::config::Config;
+use crate::history::{Edit, History};
+use crate::journal::Journal;
+use crate::keys::{Action, Keymap, CATEGORIES};
+use crate::migration::{self, LoadError, CURRENT_VERSION};
+use crate::model::{Id, Identifiable, Item, ModelError, Nameable, ProgressStore, SortKey, Target};
+use crate::render::{Hit, Renderable, Viewport};
+use crate::selection::{
+    matches, resolve_name, resolve_name_strictly, Resolved, Selection, Selector,
+};
+use crossterm::event::{
+    self, Event, KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 use serde::{Deserialize as Deserialise, Serialize as Serialise};
-use std::io::{self, Error, ErrorKind, Read, Write};
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use tui::{
     backend::Backend,
-    layout::{Constraint, Layout},
-    widgets::{Block, Borders, Paragraph, TableState},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Paragraph},
     Frame, Terminal,
 };
 use unicode_width::UnicodeWidthStr;
 
-enum InputSubject {
-    MapName,
-    ZoneName,
-    AbilityName,
-    UsageName,
+const HELP_PAGE: u16 = 10;
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const WHEEL_STEP: isize = 3;
+const MOUSE_HELP: &[(&str, &str)] = &[
+    ("Click", "Select, or add progress to the selected lineup"),
+    ("Right click", "Select and remove progress"),
+    ("Wheel", "Scroll, sideways with Shift"),
+    ("Ctrl-wheel", "Change the progress under the pointer"),
+    ("Alt-wheel", "Change the target under the pointer"),
+];
+
+fn subject_names<'a>(
+    store: &'a ProgressStore,
+    selection: &Selection,
+    subject: &InputSubject,
+) -> Vec<&'a str> {
+    let selected = store.selected(selection);
+    match subject {
+        InputSubject::Map => store.maps.iter().map(|m| m.name().as_str()).collect(),
+        InputSubject::Zone => selected
+            .map
+            .map(|m| m.zones.iter().map(|z| z.name().as_str()).collect())
+            .unwrap_or_default(),
+        InputSubject::Ability => store.abilities.iter().map(|a| a.name().as_str()).collect(),
+        InputSubject::Usage => selected
+            .ability
+            .map(|a| a.usages.iter().map(|u| u.name().as_str()).collect())
+            .unwrap_or_default(),
+    }
+}
+
+fn parse_sort_key(s: &str) -> Option<(SortKey, bool)> {
+    let (reverse, s) = match s.trim().strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s.trim()),
+    };
+    if s.is_empty() {
+        return None;
+    }
+
+    let s = s.to_lowercase();
+    let key = [
+        ("name", SortKey::Name),
+        ("alphabetical", SortKey::Name),
+        ("completion", SortKey::Completion),
+        ("progress", SortKey::Completion),
+        ("recent", SortKey::Recent),
+    ]
+    .into_iter()
+    .find(|(name, _)| name.starts_with(&s))?
+    .1;
+    Some((key, reverse))
+}
+
+#[derive(Clone, Copy)]
+pub enum InputSubject {
+    Map,
+    Zone,
+    Ability,
+    Usage,
 }
 
-enum InputOp {
+impl InputSubject {
+    fn noun(self) -> &'static str {
+        match self {
+            InputSubject::Map => "map",
+            InputSubject::Zone => "zone",
+            InputSubject::Ability => "ability",
+            InputSubject::Usage => "usage",
+        }
+    }
+
+    fn parents(self) -> &'static [InputSubject] {
+        match self {
+            InputSubject::Map | InputSubject::Ability => &[],
+            InputSubject::Zone => &[InputSubject::Map],
+            InputSubject::Usage => &[InputSubject::Ability],
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum InputOp {
     New,
     Select,
     Remove,
+    Rename,
+    Sort,
 }
 
 type InputType = (InputOp, InputSubject);
@@ -32,400 +126,1564 @@ type InputType = (InputOp, InputSubject);
 enum InputState {
     Normal,
     Edit(InputType, String),
+    Command(String),
+    Help(u16),
+    Confirm(Removal),
 }
 
 impl InputState {
     fn edit(i: InputOp, s: InputSubject) -> Self {
         InputState::Edit((i, s), "".to_string())
     }
+
+    fn edit_with(i: InputOp, s: InputSubject, buf: String) -> Self {
+        InputState::Edit((i, s), buf)
+    }
+}
+
+// A removal waiting to be confirmed
+struct Removal {
+    edit: Edit,
+    was_selected: bool,
+}
+
+impl Removal {
+    fn what(&self) -> String {
+        match &self.edit {
+            Edit::Delete { item, .. } => item.describe(),
+            edit => edit.describe(),
+        }
+    }
+
+    // Spells out what would be lost, e.g. "6 zones, 48 tracked lineups, 31 reps of progress"
+    fn losses(&self) -> String {
+        let Edit::Delete { item, targets } = &self.edit else {
+            return self.edit.describe();
+        };
+        let mut losses = match item {
+            Item::Map { map, .. } => vec![plural(map.zones.len(), "zone", "zones")],
+            Item::Ability { ability, .. } => vec![plural(ability.usages.len(), "usage", "usages")],
+            Item::Zone { .. } | Item::Usage { .. } => vec![],
+        };
+        let reps: i32 = targets.iter().map(|(_, t)| t.progress.max(0)).sum();
+        losses.push(plural(targets.len(), "tracked lineup", "tracked lineups"));
+        losses.push(plural(reps as usize, "rep of progress", "reps of progress"));
+        losses.join(", ")
+    }
+}
+
+fn plural(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+// Tab cycles through the candidates for what was typed before the first press
+struct Completion {
+    candidates: Vec<String>,
+    idx: usize,
+}
+
+impl Completion {
+    fn cycle(mut self, forward: bool) -> Self {
+        let n = self.candidates.len();
+        self.idx = if forward {
+            (self.idx + 1) % n
+        } else {
+            (self.idx + n - 1) % n
+        };
+        self
+    }
+}
+
+struct Message {
+    text: String,
+    is_error: bool,
+}
+
+impl Message {
+    fn info(text: String) -> Self {
+        Self {
+            text,
+            is_error: false,
+        }
+    }
+
+    fn error(text: String) -> Self {
+        Self {
+            text,
+            is_error: true,
+        }
+    }
+}
+
+// Why what was asked of the tracker was not done, described only once it reaches the status line
+#[derive(Debug)]
+enum AppError {
+    Model(ModelError),
+    NoMatch(&'static str, String),
+    Ambiguous(&'static str, String, Vec<String>),
+    NotWhole(&'static str, String, String),
+    Command(String),
+    UnknownOrder(String),
+    TooManyArguments(&'static str),
+    Untracked,
+    ReadOnly(String),
+    Save(io::Error),
+}
+
+impl Display for AppError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            AppError::Model(e) => e.fmt(f),
+            AppError::NoMatch(what, query) => write!(f, "no {} matches '{}'", what, query),
+            AppError::Ambiguous(what, query, names) => write!(
+                f,
+                "ambiguous {} '{}', could be any of: {}",
+                what,
+                query,
+                names.join(", ")
+            ),
+            AppError::NotWhole(what, query, name) => write!(
+                f,
+                "no {} named '{}', give the whole name of '{}' or a prefix of it",
+                what, query, name
+            ),
+            AppError::Command(e) => f.write_str(e),
+            AppError::UnknownOrder(order) => write!(
+                f,
+                "unknown order '{}', expected name, completion or recent",
+                order
+            ),
+            AppError::TooManyArguments(what) => write!(f, "too many arguments for a {}", what),
+            AppError::Untracked => f.write_str("the selected lineup is not tracked"),
+            AppError::ReadOnly(quit) => write!(
+                f,
+                "opened read-only, so changes cannot be saved; quit with {}",
+                quit
+            ),
+            AppError::Save(e) => write!(f, "could not save: {}", e),
+        }
+    }
+}
+
+impl From<ModelError> for AppError {
+    fn from(e: ModelError) -> Self {
+        AppError::Model(e)
+    }
 }
 
 pub enum FinalAction {
     None,
     Save,
+    Interrupt,
 }
 
 pub struct App {
-    state: TableState,
+    viewport: Viewport,
     pub progress: ProgressStore,
     input_state: InputState,
-    selection: Selection,
+    completion: Option<Completion>,
+    edits: History,
+    message: Option<Message>,
+    commands: Vec<String>,
+    command_pos: Option<usize>,
+    keys: Keymap,
+    confirm_removals: bool,
+    journal: Option<Journal>,
+    autosave: Option<Duration>,
+    autosaved_at: Instant,
+    saved: Option<u64>,
+    read_only: bool,
+    pub selection: Selection,
 }
 
-#[derive(Serialise, Deserialise)]
+// The version is checked before the rest of the document is read
+#[derive(Deserialise)]
 struct SaveState {
     progress: ProgressStore,
     selection: Selection,
 }
 
+#[derive(Serialise)]
+struct SavedState<'a> {
+    version: u64,
+    progress: &'a ProgressStore,
+    selection: &'a Selection,
+}
+
 impl App {
-    pub fn new() -> App {
+    pub fn new(name: String) -> App {
         App {
-            state: TableState::default(),
-            progress: ProgressStore::new("Progress".into()),
+            viewport: Viewport::default(),
+            progress: ProgressStore::new(name),
             input_state: InputState::Normal,
+            completion: None,
+            edits: History::default(),
+            message: None,
+            commands: Vec::new(),
+            command_pos: None,
+            keys: Keymap::default(),
+            confirm_removals: true,
+            journal: None,
+            autosave: None,
+            autosaved_at: Instant::now(),
+            saved: None,
+            read_only: false,
             selection: Selection::new(),
         }
     }
 
-    pub fn load<R>(r: R) -> io::Result<Self>
+    pub fn configure(&mut self, config: Config) {
+        self.keys = config.keys;
+        self.confirm_removals = config.confirm_removals;
+        self.progress.case_insensitive_names = config.case_insensitive_names;
+        self.autosave = config.autosave;
+    }
+
+    pub fn journal_to(&mut self, journal: Journal) {
+        self.journal = Some(journal);
+    }
+
+    // For when another tracker has the save file open
+    pub fn open_read_only(&mut self) {
+        self.read_only = true;
+        self.autosave = None;
+    }
+
+    pub fn load<R>(mut r: R) -> Result<Self, LoadError>
     where
         R: Read,
     {
-        let save_state: SaveState = serde_json::from_reader(r)?;
+        let mut data = Vec::new();
+        r.read_to_end(&mut data)?;
+
+        // Documents already in the current format are parsed directly so that errors carry positions
+        let doc: Value = serde_json::from_slice(&data)?;
+        let save_state: SaveState = if migration::version(&doc) == CURRENT_VERSION {
+            serde_json::from_slice(&data)?
+        } else {
+            serde_json::from_value(migration::migrate(doc)?)?
+        };
         Ok(save_state.into())
     }
 
-    pub fn save<W>(self, w: W) -> Result<(), serde_json::Error>
+    pub fn salvage(data: &[u8], default_name: &str) -> Option<(Self, Vec<String>)> {
+        let doc: Value = serde_json::from_slice(data).ok()?;
+        let v = migration::migrate(doc).ok()?;
+        let mut losses = Vec::new();
+        let progress = match v.get("progress") {
+            Some(p) => ProgressStore::salvage(p, default_name, &mut losses),
+            None => return None,
+        };
+        let selection = v
+            .get("selection")
+            .and_then(|s| Selection::deserialize(s).ok())
+            .unwrap_or_else(|| {
+                losses.push("the current selection".into());
+                Selection::new()
+            });
+
+        let app = SaveState {
+            progress,
+            selection,
+        }
+        .into();
+        Some((app, losses))
+    }
+
+    pub fn save<W>(&self, w: W) -> Result<(), serde_json::Error>
     where
         W: Write,
     {
-        let save_state: SaveState = self.into();
-        serde_json::to_writer(w, &save_state)
+        let saved = SavedState {
+            version: CURRENT_VERSION,
+            progress: &self.progress,
+            selection: &self.selection,
+        };
+        serde_json::to_writer(w, &saved)
     }
 
-    pub fn run<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> io::Result<FinalAction> {
+    pub fn run<B, S, R>(
+        &mut self,
+        terminal: &mut Terminal<B>,
+        stop: &AtomicBool,
+        changed: &AtomicBool,
+        mut save: S,
+        mut reload: R,
+    ) -> io::Result<FinalAction>
+    where
+        B: Backend,
+        S: FnMut(&App) -> io::Result<()>,
+        R: FnMut(&mut ProgressStore) -> io::Result<Option<usize>>,
+    {
+        self.saved = self.fingerprint();
+        self.autosaved_at = Instant::now();
         loop {
             terminal.draw(|f| self.draw(f))?;
 
-            match event::read()? {
-                Event::Key(key) => match self.input_state {
-                    InputState::Normal => match key.code {
-                        KeyCode::Char('Q') => return Ok(FinalAction::Save),
-                        KeyCode::Char('!') => return Ok(FinalAction::None),
-                        KeyCode::Char('y') => {
-                            self.progress
-                                .get_target_mut(&self.selection)
-                                .map(|t| t.change_progress(1));
-                        }
-                        KeyCode::Char('Y') => {
-                            self.progress
-                                .get_target_mut(&self.selection)
-                                .map(|t| t.change_progress(-1));
-                        }
-                        KeyCode::Char('u') => {
-                            self.progress
-                                .get_target_mut(&self.selection)
-                                .map(|t| t.change_target(1));
-                        }
-                        KeyCode::Char('U') => {
-                            self.progress
-                                .get_target_mut(&self.selection)
-                                .map(|t| t.change_target(-1));
-                        }
-                        KeyCode::Char('I') => {
-                            self.progress
-                                .get_target_mut(&self.selection)
-                                .map(|t| t.match_target_to_progress());
-                        }
-                        KeyCode::Char('i') => {
-                            self.progress
-                                .get_target_mut(&self.selection)
-                                .map(|t| t.match_progress_to_target());
-                        }
-                        KeyCode::Char('o') => {
-                            self.progress
-                                .get_target_mut(&self.selection)
-                                .map(|t| t.zero_target());
-                        }
-                        KeyCode::Char('O') => {
-                            self.progress
-                                .get_target_mut(&self.selection)
-                                .map(|t| t.zero_progress());
-                        }
-                        KeyCode::Char('q') => {
-                            self.input_state = InputState::edit(InputOp::New, InputSubject::MapName)
-                        }
-                        KeyCode::Char('w') => {
-                            self.input_state =
-                                InputState::edit(InputOp::New, InputSubject::ZoneName)
-                        }
-                        KeyCode::Char('e') => {
-                            self.input_state =
-                                InputState::edit(InputOp::New, InputSubject::AbilityName)
-                        }
-                        KeyCode::Char('r') => {
-                            self.input_state =
-                                InputState::edit(InputOp::New, InputSubject::UsageName)
-                        }
-                        KeyCode::Char('a') => {
-                            self.input_state =
-                                InputState::edit(InputOp::Select, InputSubject::MapName)
-                        }
-                        KeyCode::Char('s') => {
-                            self.input_state =
-                                InputState::edit(InputOp::Select, InputSubject::ZoneName)
-                        }
-                        KeyCode::Char('d') => {
-                            self.input_state =
-                                InputState::edit(InputOp::Select, InputSubject::AbilityName)
-                        }
-                        KeyCode::Char('f') => {
-                            self.input_state =
-                                InputState::edit(InputOp::Select, InputSubject::UsageName)
-                        }
-                        KeyCode::Char('z') => {
-                            self.input_state =
-                                InputState::edit(InputOp::Remove, InputSubject::MapName)
-                        }
-                        KeyCode::Char('x') => {
-                            self.input_state =
-                                InputState::edit(InputOp::Remove, InputSubject::ZoneName)
-                        }
-                        KeyCode::Char('c') => {
-                            if key.modifiers.contains(KeyModifiers::CONTROL) {
-                                return Err(Error::new(ErrorKind::Other, "SIGINT Caught!"));
-                            }
+            // Poll rather than block so that signals and autosaves happen on time
+            if stop.load(Ordering::Relaxed) {
+                return Ok(FinalAction::Interrupt);
+            }
+            if !event::poll(POLL_INTERVAL)? {
+                self.reload(changed, &mut reload);
+                self.autosave(&mut save);
+                continue;
+            }
+            let key = match event::read()? {
+                Event::Key(key) => key,
+                Event::Mouse(mouse) => {
+                    self.mouse(mouse);
+                    continue;
+                }
+                _ => continue,
+            };
+            self.message = None;
+            match key.code {
+                KeyCode::Tab | KeyCode::BackTab if self.completing().is_some() => {
+                    self.complete(key.code == KeyCode::Tab);
+                    continue;
+                }
+                _ => self.completion = None,
+            }
 
-                            self.input_state =
-                                InputState::edit(InputOp::Remove, InputSubject::AbilityName)
-                        }
-                        KeyCode::Char('v') => {
-                            self.input_state =
-                                InputState::edit(InputOp::Remove, InputSubject::UsageName)
-                        }
-                        KeyCode::Left | KeyCode::Char('h') => {
-                            self.selection.prev_usage(&self.progress.abilities)
-                        }
-                        KeyCode::Down | KeyCode::Char('j') => {
-                            self.selection.next_zone(&self.progress.maps)
-                        }
-                        KeyCode::Up | KeyCode::Char('k') => {
-                            self.selection.prev_zone(&self.progress.maps)
-                        }
-                        KeyCode::Right | KeyCode::Char('l') => {
-                            self.selection.next_usage(&self.progress.abilities)
+            match self.input_state {
+                InputState::Normal => match self.keys.action(&key) {
+                    Some(Action::Help) => self.input_state = InputState::Help(0),
+                    Some(Action::Undo) => self.undo(false),
+                    Some(Action::Redo) => self.undo(true),
+                    Some(Action::AbbreviateUsages) => {
+                        let abbreviate = !self.viewport.abbreviate_usages;
+                        self.viewport.abbreviate_usages = abbreviate;
+                        let text = match abbreviate {
+                            true => "abbreviating usage names",
+                            false => "showing full usage names",
+                        };
+                        self.message = Some(Message::info(text.to_string()));
+                    }
+                    Some(Action::CommandLine) => {
+                        self.command_pos = None;
+                        self.input_state = InputState::Command(String::new())
+                    }
+                    Some(Action::SaveAndQuit) if self.read_only => {
+                        self.message = Some(Message::error(self.read_only_error().to_string()))
+                    }
+                    Some(Action::SaveAndQuit) => return Ok(FinalAction::Save),
+                    Some(Action::Quit) => return Ok(FinalAction::None),
+                    Some(Action::Interrupt) => return Ok(FinalAction::Interrupt),
+                    Some(Action::PrevZone) => self.selection.prev_zone(&self.progress.maps),
+                    Some(Action::NextZone) => self.selection.next_zone(&self.progress.maps),
+                    Some(Action::PrevUsage) => self.selection.prev_usage(&self.progress.abilities),
+                    Some(Action::NextUsage) => self.selection.next_usage(&self.progress.abilities),
+                    Some(Action::IncrProgress) => self.update_target(|t| t.change_progress(1)),
+                    Some(Action::DecrProgress) => self.update_target(|t| t.change_progress(-1)),
+                    Some(Action::IncrTarget) => self.update_target(|t| t.change_target(1)),
+                    Some(Action::DecrTarget) => self.update_target(|t| t.change_target(-1)),
+                    Some(Action::MatchProgressToTarget) => {
+                        self.update_target(Target::match_progress_to_target)
+                    }
+                    Some(Action::MatchTargetToProgress) => {
+                        self.update_target(Target::match_target_to_progress)
+                    }
+                    Some(Action::ZeroTarget) => self.update_target(Target::zero_target),
+                    Some(Action::ZeroProgress) => self.update_target(Target::zero_progress),
+                    Some(Action::NewMap) => self.start_input(InputOp::New, InputSubject::Map),
+                    Some(Action::NewZone) => self.start_input(InputOp::New, InputSubject::Zone),
+                    Some(Action::NewAbility) => {
+                        self.start_input(InputOp::New, InputSubject::Ability)
+                    }
+                    Some(Action::NewUsage) => self.start_input(InputOp::New, InputSubject::Usage),
+                    Some(Action::SelectMap) => self.start_input(InputOp::Select, InputSubject::Map),
+                    Some(Action::SelectZone) => {
+                        self.start_input(InputOp::Select, InputSubject::Zone)
+                    }
+                    Some(Action::SelectAbility) => {
+                        self.start_input(InputOp::Select, InputSubject::Ability)
+                    }
+                    Some(Action::SelectUsage) => {
+                        self.start_input(InputOp::Select, InputSubject::Usage)
+                    }
+                    Some(Action::RemoveMap) => self.start_input(InputOp::Remove, InputSubject::Map),
+                    Some(Action::RemoveZone) => {
+                        self.start_input(InputOp::Remove, InputSubject::Zone)
+                    }
+                    Some(Action::RemoveAbility) => {
+                        self.start_input(InputOp::Remove, InputSubject::Ability)
+                    }
+                    Some(Action::RemoveUsage) => {
+                        self.start_input(InputOp::Remove, InputSubject::Usage)
+                    }
+                    Some(Action::RenameMap) => self.start_rename(InputSubject::Map),
+                    Some(Action::RenameZone) => self.start_rename(InputSubject::Zone),
+                    Some(Action::RenameAbility) => self.start_rename(InputSubject::Ability),
+                    Some(Action::RenameUsage) => self.start_rename(InputSubject::Usage),
+                    Some(Action::MoveMapUp) => self.move_selected(InputSubject::Map, -1),
+                    Some(Action::MoveMapDown) => self.move_selected(InputSubject::Map, 1),
+                    Some(Action::MoveZoneUp) => self.move_selected(InputSubject::Zone, -1),
+                    Some(Action::MoveZoneDown) => self.move_selected(InputSubject::Zone, 1),
+                    Some(Action::MoveAbilityLeft) => self.move_selected(InputSubject::Ability, -1),
+                    Some(Action::MoveAbilityRight) => self.move_selected(InputSubject::Ability, 1),
+                    Some(Action::MoveUsageLeft) => self.move_selected(InputSubject::Usage, -1),
+                    Some(Action::MoveUsageRight) => self.move_selected(InputSubject::Usage, 1),
+                    Some(Action::SortRows) => self.start_input(InputOp::Sort, InputSubject::Map),
+                    Some(Action::SortColumns) => {
+                        self.start_input(InputOp::Sort, InputSubject::Ability)
+                    }
+                    None => {}
+                },
+                InputState::Command(ref mut buf) => match key.code {
+                    KeyCode::Char('[') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        self.input_state = InputState::Normal
+                    }
+                    KeyCode::Char(c) => buf.push(c),
+                    KeyCode::Backspace if buf.is_empty() => self.input_state = InputState::Normal,
+                    KeyCode::Backspace => {
+                        buf.pop();
+                    }
+                    KeyCode::Up => self.recall(-1),
+                    KeyCode::Down => self.recall(1),
+                    KeyCode::Enter => {
+                        let line = buf.clone();
+                        self.input_state = InputState::Normal;
+                        if let Some(action) = self.execute(&line, &mut save) {
+                            return Ok(action);
                         }
-                        _ => {}
-                    },
-                    InputState::Edit(ref op, ref mut buf) => match key.code {
-                        KeyCode::Char(c) => {
-                            if key.modifiers.contains(KeyModifiers::CONTROL) && c == '[' {
-                                self.input_state = InputState::Normal;
-                            } else {
-                                buf.push(c);
-                            }
+                    }
+                    KeyCode::Esc => self.input_state = InputState::Normal,
+                    _ => {}
+                },
+                InputState::Help(ref mut scroll) => match key.code {
+                    KeyCode::Esc | KeyCode::Char('?') | KeyCode::Char('q') => {
+                        self.input_state = InputState::Normal
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => *scroll = scroll.saturating_add(1),
+                    KeyCode::Up | KeyCode::Char('k') => *scroll = scroll.saturating_sub(1),
+                    KeyCode::PageDown | KeyCode::Char(' ') => {
+                        *scroll = scroll.saturating_add(HELP_PAGE)
+                    }
+                    KeyCode::PageUp => *scroll = scroll.saturating_sub(HELP_PAGE),
+                    KeyCode::Home | KeyCode::Char('g') => *scroll = 0,
+                    _ => {}
+                },
+                InputState::Confirm(_) => match key.code {
+                    KeyCode::Char('y' | 'Y') | KeyCode::Enter => {
+                        if let InputState::Confirm(removal) =
+                            std::mem::replace(&mut self.input_state, InputState::Normal)
+                        {
+                            self.finish_removal(removal);
                         }
-                        KeyCode::Backspace => {
-                            buf.pop();
+                    }
+                    KeyCode::Char('n' | 'N' | 'q') | KeyCode::Esc => {
+                        if let InputState::Confirm(removal) =
+                            std::mem::replace(&mut self.input_state, InputState::Normal)
+                        {
+                            let text = format!("kept {}", removal.what());
+                            self.message = Some(Message::info(text));
                         }
-                        KeyCode::Enter => {
-                            match op {
-                                (InputOp::New, what) => {
-                                    lazy_static! {
-                                        static ref NUM_RE: Regex = Regex::new("^[0-9]+$").unwrap();
-                                    }
-                                    if NUM_RE.is_match(&buf) {
-                                        continue;
-                                    }
-                                    match what {
-                                        InputSubject::MapName => {
-                                            self.progress.add_map(Map::new(buf.clone()));
-                                            self.selection.map = Some(Selector::Name(buf.clone()));
-                                            self.selection.zone = None;
-                                        }
-                                        InputSubject::ZoneName => {
-                                            if let Some(mi) = &self.selection.map {
-                                                let zone_selector = Selector::Name(buf.clone());
-                                                let zone = Zone::new(buf.clone());
-
-                                                if let Some(map) = mi.get_selected_mut::<Map>(
-                                                    &mut self.progress.maps,
-                                                ) {
-                                                    map.add_zone(zone);
-                                                }
-
-                                                self.progress.add_zone(&mi, &zone_selector);
-                                                self.selection.zone = Some(zone_selector);
-                                            }
-                                        }
-                                        InputSubject::AbilityName => {
-                                            self.progress.add_ability(Ability::new(buf.clone()));
-                                            self.selection.ability =
-                                                Some(Selector::Name(buf.clone()));
-                                            self.selection.usage = None;
-                                        }
-                                        InputSubject::UsageName => {
-                                            if let Some(asel) = &self.selection.ability {
-                                                let usage_selector = Selector::Name(buf.clone());
-                                                let usage = Usage::new(buf.clone());
-
-                                                if let Some(ability) = asel
-                                                    .get_selected_mut(&mut self.progress.abilities)
-                                                {
-                                                    ability.add_usage(usage);
-                                                }
-
-                                                self.progress.add_usage(&asel, &usage_selector);
-                                                self.selection.usage = Some(usage_selector);
-                                            }
-                                        }
-                                    }
-                                }
-                                (InputOp::Select, subject) => {
-                                    match subject {
-                                        InputSubject::MapName => {
-                                            self.selection.map = Some(buf.clone().into())
-                                        }
-                                        InputSubject::ZoneName => {
-                                            self.selection.zone = Some(buf.clone().into())
-                                        }
-                                        InputSubject::AbilityName => {
-                                            self.selection.ability = Some(buf.clone().into())
-                                        }
-                                        InputSubject::UsageName => {
-                                            self.selection.usage = Some(buf.clone().into())
-                                        }
-                                    };
-                                    self.selection = self
-                                        .selection
-                                        .relative(&self.progress.maps, &self.progress.abilities);
-                                }
-                                (InputOp::Remove, InputSubject::MapName) => {
-                                    if let Some(Some(m)) =
-                                        self.selection.map.as_ref().map(|msel| {
-                                            msel.get_selected_mut(&mut self.progress.maps)
-                                        })
-                                    {
-                                        if m.name() == buf {
-                                            self.selection = Selection::default();
-                                        }
-                                        self.progress.rm_map(&buf);
-                                    }
-                                }
-                                (InputOp::Remove, InputSubject::ZoneName) => {
-                                    if let Some(Some(map)) =
-                                        self.selection.map.as_ref().map(|msel| {
-                                            msel.get_selected_mut(&mut self.progress.maps)
-                                        })
-                                    {
-                                        if let Some(Some(z)) = self
-                                            .selection
-                                            .zone
-                                            .as_ref()
-                                            .map(|zsel| zsel.get_selected(&map.zones))
-                                        {
-                                            if z.name() == buf {
-                                                self.selection = Selection::default();
-                                            }
-                                        }
-                                    }
-                                    if let Some(Some(Selector::Name(ref map))) = self
-                                        .selection
-                                        .map
-                                        .as_ref()
-                                        .map(|msel| msel.to_name(&self.progress.maps))
-                                    {
-                                        self.progress.rm_zone(map, &buf);
-                                    }
-                                }
-                                (InputOp::Remove, InputSubject::AbilityName) => {
-                                    if let Some(Some(a)) =
-                                        self.selection.ability.as_ref().map(|asel| {
-                                            asel.get_selected_mut(&mut self.progress.abilities)
-                                        })
-                                    {
-                                        if a.name() == buf {
-                                            self.selection = Selection::default();
-                                        }
-                                        self.progress.rm_ability(&buf);
-                                    }
-                                }
-                                (InputOp::Remove, InputSubject::UsageName) => {
-                                    if let Some(Some(ability)) =
-                                        self.selection.ability.as_ref().map(|asel| {
-                                            asel.get_selected_mut(&mut self.progress.abilities)
-                                        })
-                                    {
-                                        if let Some(Some(u)) = self
-                                            .selection
-                                            .usage
-                                            .as_ref()
-                                            .map(|usel| usel.get_selected(&ability.usages))
-                                        {
-                                            if u.name() == buf {
-                                                self.selection = Selection::default();
-                                            }
-                                        }
-                                    }
-                                    if let Some(Some(Selector::Name(ref ability))) = self
-                                        .selection
-                                        .ability
-                                        .as_ref()
-                                        .map(|msel| msel.to_name(&self.progress.abilities))
-                                    {
-                                        self.progress.rm_usage(ability, &buf);
-                                    }
-                                }
-                            };
+                    }
+                    _ => {}
+                },
+                InputState::Edit(ref op, ref mut buf) => match key.code {
+                    KeyCode::Char(c) => {
+                        if key.modifiers.contains(KeyModifiers::CONTROL) && c == '[' {
                             self.input_state = InputState::Normal;
+                        } else {
+                            buf.push(c);
                         }
-                        KeyCode::Esc => self.input_state = InputState::Normal,
-                        _ => {}
-                    },
+                    }
+                    KeyCode::Backspace => {
+                        buf.pop();
+                    }
+                    KeyCode::Enter => {
+                        let (op, subject) = *op;
+                        let input = buf.clone();
+                        // Submitting may move on to another state, such as confirmation
+                        let editing = std::mem::replace(&mut self.input_state, InputState::Normal);
+                        if let Err(e) = self.submit(op, subject, &input) {
+                            self.input_state = editing;
+                            self.message = Some(Message::error(e.to_string()));
+                        }
+                    }
+                    KeyCode::Esc => self.input_state = InputState::Normal,
+                    _ => {}
                 },
-                _ => {}
             }
         }
     }
 
-    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
-        let rect_constraints;
-        if let InputState::Edit(_, _) = self.input_state {
-            rect_constraints = [Constraint::Percentage(95), Constraint::Min(3)].as_ref();
+    fn submit(&mut self, op: InputOp, subject: InputSubject, input: &str) -> Result<(), AppError> {
+        match op {
+            InputOp::New => self.add(subject, input),
+            InputOp::Select => self.select(subject, input),
+            InputOp::Remove => self.remove(subject, input),
+            InputOp::Rename => self.rename(subject, input),
+            InputOp::Sort => self.sort(subject, input),
+        }
+    }
+
+    fn add(&mut self, subject: InputSubject, name: &str) -> Result<(), AppError> {
+        let name = name.to_string();
+        let id = match subject {
+            InputSubject::Map => {
+                let id = self.progress.add_map(name.clone())?;
+                self.selection.map = Some(Selector::Name(name));
+                self.selection.zone = None;
+                id
+            }
+            InputSubject::Zone => {
+                let msel = self
+                    .selection
+                    .map
+                    .as_ref()
+                    .ok_or(ModelError::NoSelection("map"))?;
+                let id = self.progress.add_zone(msel, name.clone())?;
+                self.selection.zone = Some(Selector::Name(name));
+                id
+            }
+            InputSubject::Ability => {
+                let id = self.progress.add_ability(name.clone())?;
+                self.selection.ability = Some(Selector::Name(name));
+                self.selection.usage = None;
+                id
+            }
+            InputSubject::Usage => {
+                let asel = self
+                    .selection
+                    .ability
+                    .as_ref()
+                    .ok_or(ModelError::NoSelection("ability"))?;
+                let id = self.progress.add_usage(asel, name.clone())?;
+                self.selection.usage = Some(Selector::Name(name));
+                id
+            }
+        };
+        if let Some(edit) = Edit::insertion(&self.progress, id) {
+            self.edits.record(edit);
+        }
+        if let Some(item) = self.progress.item(id) {
+            self.message = Some(Message::info(format!("added {}", item.describe())));
+        }
+        Ok(())
+    }
+
+    // Finds the full name of what the query abbreviates, or checks that an index exists
+    fn resolve(&self, subject: InputSubject, query: &str) -> Result<Selector, AppError> {
+        self.resolve_with(subject, query, resolve_name)
+    }
+
+    // Removals and renames only take whole names or prefixes, as a loose match may be a typo
+    fn resolve_strictly(&self, subject: InputSubject, query: &str) -> Result<Selector, AppError> {
+        self.resolve_with(subject, query, resolve_name_strictly)
+            .map_err(|e| match self.resolve(subject, query) {
+                Ok(Selector::Name(name)) if !query.trim().is_empty() => {
+                    AppError::NotWhole(subject.noun(), query.to_string(), name)
+                }
+                _ => e,
+            })
+    }
+
+    fn resolve_with(
+        &self,
+        subject: InputSubject,
+        query: &str,
+        resolve: fn(&str, &[&str]) -> Resolved,
+    ) -> Result<Selector, AppError> {
+        let names = subject_names(&self.progress, &self.selection, &subject);
+        match Selector::from(query.to_string()) {
+            Selector::Name(query) => match resolve(&query, &names) {
+                Resolved::Found(i) => Ok(Selector::Name(names[i].to_string())),
+                Resolved::Ambiguous(is) => {
+                    let names = is.iter().map(|i| names[*i].to_string()).collect();
+                    Err(AppError::Ambiguous(subject.noun(), query, names))
+                }
+                Resolved::Missing => Err(AppError::NoMatch(subject.noun(), query)),
+            },
+            Selector::Index(i) if i < names.len() => Ok(Selector::Index(i)),
+            Selector::Index(i) => Err(ModelError::OutOfRange(subject.noun(), i).into()),
+        }
+    }
+
+    fn select(&mut self, subject: InputSubject, query: &str) -> Result<(), AppError> {
+        let sel = self.resolve(subject, query)?;
+        self.select_resolved(subject, sel);
+        Ok(())
+    }
+
+    fn select_resolved(&mut self, subject: InputSubject, sel: Selector) {
+        *self.selector_mut(subject) = Some(sel);
+        self.selection = self
+            .selection
+            .relative(&self.progress.maps, &self.progress.abilities);
+    }
+
+    fn remove(&mut self, subject: InputSubject, query: &str) -> Result<(), AppError> {
+        let name = match self.resolve_strictly(subject, query)? {
+            Selector::Name(name) => name,
+            Selector::Index(i) => {
+                subject_names(&self.progress, &self.selection, &subject)[i].to_string()
+            }
+        };
+        let name = name.as_str();
+        let selected = self.progress.selected(&self.selection);
+        let id = match subject {
+            InputSubject::Map => Self::id_named(&self.progress.maps, name),
+            InputSubject::Zone => selected.map.and_then(|m| Self::id_named(&m.zones, name)),
+            InputSubject::Ability => Self::id_named(&self.progress.abilities, name),
+            InputSubject::Usage => selected
+                .ability
+                .and_then(|a| Self::id_named(&a.usages, name)),
+        };
+        let was_selected = match subject {
+            InputSubject::Map => selected.map.map(|m| m.id),
+            InputSubject::Zone => selected.zone.map(|z| z.id),
+            InputSubject::Ability => selected.ability.map(|a| a.id),
+            InputSubject::Usage => selected.usage.map(|u| u.id),
+        }
+        .is_some_and(|selected| Some(selected) == id);
+
+        let edit = id
+            .and_then(|id| Edit::deletion(&self.progress, id))
+            .ok_or_else(|| ModelError::Unknown(subject.noun(), name.to_string()))?;
+        let removal = Removal { edit, was_selected };
+        if self.confirm_removals {
+            self.input_state = InputState::Confirm(removal);
         } else {
-            rect_constraints = [Constraint::Percentage(100)].as_ref();
+            self.finish_removal(removal);
+        }
+        Ok(())
+    }
+
+    fn finish_removal(&mut self, removal: Removal) {
+        let what = removal.what();
+        if let Err(e) = self.perform(removal.edit) {
+            self.message = Some(Message::error(e.to_string()));
+            return;
+        }
+        self.message = Some(Message::info(format!("removed {}", what)));
+        if removal.was_selected {
+            self.selection = Selection::default();
+        }
+    }
+
+    fn id_named<T: Nameable + Identifiable>(vs: &[T], name: &str) -> Option<Id> {
+        vs.iter().find(|v| v.name() == name).map(|v| v.id())
+    }
+
+    fn rename(&mut self, subject: InputSubject, name: &str) -> Result<(), AppError> {
+        let selected = self.progress.selected(&self.selection);
+        let (id, from) = match subject {
+            InputSubject::Map => selected.map.map(|m| (m.id, m.name.clone())),
+            InputSubject::Zone => selected.zone.map(|z| (z.id, z.name.clone())),
+            InputSubject::Ability => selected.ability.map(|a| (a.id, a.name.clone())),
+            InputSubject::Usage => selected.usage.map(|u| (u.id, u.name.clone())),
         }
+        .ok_or(ModelError::NoSelection(subject.noun()))?;
+
+        self.perform(Edit::Rename {
+            id,
+            from: from.clone(),
+            to: name.to_string(),
+        })?;
+        if let Some(sel) = self.selector_mut(subject) {
+            sel.rename(&from, name);
+        }
+        let text = format!("renamed '{}' to '{}'", from, name);
+        self.message = Some(Message::info(text));
+        Ok(())
+    }
+
+    fn selector_mut(&mut self, subject: InputSubject) -> &mut Option<Selector> {
+        match subject {
+            InputSubject::Map => &mut self.selection.map,
+            InputSubject::Zone => &mut self.selection.zone,
+            InputSubject::Ability => &mut self.selection.ability,
+            InputSubject::Usage => &mut self.selection.usage,
+        }
+    }
+
+    fn perform(&mut self, edit: Edit) -> Result<(), ModelError> {
+        edit.apply(&mut self.progress)?;
+        self.journal(&edit);
+        self.edits.record(edit);
+        Ok(())
+    }
+
+    // Changes to targets are the most frequent, so they reach the disk before the next save does
+    fn journal(&mut self, edit: &Edit) {
+        let (Some(journal), Edit::Target { key, to, .. }) = (&self.journal, edit) else {
+            return;
+        };
+        if let Err(e) = journal.append(*key, to) {
+            self.message = Some(Message::error(format!("could not journal change: {}", e)));
+        }
+    }
+
+    // Takes in changes made to the save file elsewhere, which are merged with any made here
+    fn reload<R>(&mut self, changed: &AtomicBool, reload: &mut R)
+    where
+        R: FnMut(&mut ProgressStore) -> io::Result<Option<usize>>,
+    {
+        if !changed.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let selection = self
+            .selection
+            .relative(&self.progress.maps, &self.progress.abilities);
+        let clean = self.fingerprint() == self.saved;
+        let reloaded = reload(&mut self.progress);
+        // Edits made before the reload may refer to what was removed elsewhere
+        if let Ok(Some(_)) = reloaded {
+            self.edits.clear();
+        }
+        let text = match reloaded {
+            Ok(None) => return,
+            Ok(Some(0)) => Message::info("reloaded changes made elsewhere".to_string()),
+            Ok(Some(n)) => Message::error(format!(
+                "reloaded changes made elsewhere; {} changed in both kept this progress",
+                plural(n, "lineup", "lineups")
+            )),
+            Err(e) => Message::error(format!("could not reload: {}", e)),
+        };
+        self.message = Some(text);
+        self.selection = selection;
+        if clean {
+            self.saved = self.fingerprint();
+        }
+    }
+
+    fn autosave<S>(&mut self, save: &mut S)
+    where
+        S: FnMut(&App) -> io::Result<()>,
+    {
+        let Some(interval) = self.autosave else {
+            return;
+        };
+        if self.autosaved_at.elapsed() < interval {
+            return;
+        }
+        self.autosaved_at = Instant::now();
+
+        let fingerprint = self.fingerprint();
+        if fingerprint == self.saved {
+            return;
+        }
+        match save(self) {
+            Ok(()) => self.saved = fingerprint,
+            Err(e) => self.message = Some(Message::error(format!("could not autosave: {}", e))),
+        }
+    }
+
+    fn read_only_error(&self) -> AppError {
+        AppError::ReadOnly(match self.keys.keys_for(Action::Quit).first() {
+            Some(key) => format!("{} or :q!", key),
+            None => ":q!".to_string(),
+        })
+    }
+
+    fn fingerprint(&self) -> Option<u64> {
+        let mut data = Vec::new();
+        self.save(&mut data).ok()?;
+        let mut h = DefaultHasher::new();
+        data.hash(&mut h);
+        Some(h.finish())
+    }
+
+    // Records the reordering made by the given change, if any
+    fn rearrange<F>(&mut self, change: F) -> Result<(), ModelError>
+    where
+        F: FnOnce(&mut ProgressStore) -> Result<(), ModelError>,
+    {
+        let from = self.progress.arrangement();
+        let res = change(&mut self.progress);
+        let to = self.progress.arrangement();
+        if from != to {
+            self.edits.record(Edit::Arrange { from, to });
+        }
+        res
+    }
+
+    fn undo(&mut self, redo: bool) {
+        let edit = match redo {
+            false => self.edits.undo(&mut self.progress),
+            true => self.edits.redo(&mut self.progress),
+        };
+        let what = if redo { "redo" } else { "undo" };
+        let edit = match edit {
+            Ok(Some(edit)) => edit.clone(),
+            Ok(None) => {
+                self.message = Some(Message::info(format!("nothing to {}", what)));
+                return;
+            }
+            Err(e) => {
+                self.message = Some(Message::error(format!("could not {}: {}", what, e)));
+                return;
+            }
+        };
+
+        // Selections by name follow whatever was renamed
+        let applied = if redo { edit.clone() } else { edit.inverse() };
+        self.journal(&applied);
+        if let Edit::Rename { id, from, to } = &applied {
+            let subject = match self.progress.item(*id) {
+                Some(Item::Map { .. }) => InputSubject::Map,
+                Some(Item::Zone { .. }) => InputSubject::Zone,
+                Some(Item::Ability { .. }) => InputSubject::Ability,
+                _ => InputSubject::Usage,
+            };
+            if let Some(sel) = self.selector_mut(subject) {
+                sel.rename(from, to);
+            }
+        }
+
+        let done = if redo { "redid" } else { "undid" };
+        self.message = Some(Message::info(format!("{} {}", done, edit.describe())));
+    }
+
+    fn sort(&mut self, subject: InputSubject, order: &str) -> Result<(), AppError> {
+        let (key, reverse) =
+            parse_sort_key(order).ok_or_else(|| AppError::UnknownOrder(order.to_string()))?;
+        self.selection = self
+            .selection
+            .relative(&self.progress.maps, &self.progress.abilities);
+        self.rearrange(|store| {
+            match subject {
+                InputSubject::Map | InputSubject::Zone => store.sort_rows(key, reverse),
+                InputSubject::Ability | InputSubject::Usage => store.sort_columns(key, reverse),
+            }
+            Ok(())
+        })?;
+        let axis = match subject {
+            InputSubject::Map | InputSubject::Zone => "rows",
+            InputSubject::Ability | InputSubject::Usage => "columns",
+        };
+        self.message = Some(Message::info(format!("sorted {} by {}", axis, order)));
+        Ok(())
+    }
+
+    fn recall(&mut self, delta: isize) {
+        let InputState::Command(buf) = &mut self.input_state else {
+            return;
+        };
+        let pos = match (self.command_pos, delta < 0) {
+            (None, true) => self.commands.len().checked_sub(1),
+            (None, false) => None,
+            (Some(pos), true) => Some(pos.saturating_sub(1)),
+            (Some(pos), false) => Some(pos + 1).filter(|p| *p < self.commands.len()),
+        };
+        if pos.is_none() && self.command_pos.is_none() {
+            return;
+        }
+        *buf = pos.map(|p| self.commands[p].clone()).unwrap_or_default();
+        self.command_pos = pos;
+    }
+
+    fn execute<S>(&mut self, line: &str, save: &mut S) -> Option<FinalAction>
+    where
+        S: FnMut(&App) -> io::Result<()>,
+    {
+        if !line.trim().is_empty() && self.commands.last().is_none_or(|l| l != line) {
+            self.commands.push(line.to_string());
+        }
+
+        let res = match command_line::parse(line) {
+            Ok(Some(Line::Quit { save: true })) if self.read_only => Err(self.read_only_error()),
+            Ok(Some(Line::Quit { save: true })) => return Some(FinalAction::Save),
+            Ok(Some(Line::Quit { save: false })) => return Some(FinalAction::None),
+            Ok(Some(parsed)) => self.run_line(parsed, save),
+            Ok(None) => Ok(None),
+            Err(e) => Err(AppError::Command(e)),
+        };
+        match res {
+            Ok(Some(text)) => self.message = Some(Message::info(text)),
+            Ok(None) => {}
+            Err(e) => self.message = Some(Message::error(e.to_string())),
+        }
+        None
+    }
+
+    fn run_line<S>(&mut self, line: Line, save: &mut S) -> Result<Option<String>, AppError>
+    where
+        S: FnMut(&App) -> io::Result<()>,
+    {
+        match line {
+            Line::Input(op, subject, args) => {
+                let Some((last, path)) = args.split_last() else {
+                    return Ok(None);
+                };
+
+                // Leading arguments select what is above the subject, nearest last
+                let mut levels = subject.parents().to_vec();
+                if let InputOp::Rename = op {
+                    levels.push(subject);
+                }
+                if let InputOp::Sort = op {
+                    levels.clear();
+                }
+                if path.len() > levels.len() {
+                    return Err(AppError::TooManyArguments(subject.noun()));
+                }
+                for (level, query) in levels[levels.len() - path.len()..].iter().zip(path) {
+                    let sel = match op {
+                        InputOp::Remove | InputOp::Rename => {
+                            self.resolve_strictly(*level, query)?
+                        }
+                        _ => self.resolve(*level, query)?,
+                    };
+                    self.select_resolved(*level, sel);
+                }
+                self.submit(op, subject, last)?;
+                Ok(None)
+            }
+            Line::Progress(change) => self.change_selected(|t| match change {
+                Change::By(n) => t.change_progress(n),
+                Change::To(n) => t.set_progress(n),
+            }),
+            Line::Target(change) => self.change_selected(|t| match change {
+                Change::By(n) => t.change_target(n),
+                Change::To(n) => t.set_target(n),
+            }),
+            Line::List(subject) => {
+                let names = subject_names(&self.progress, &self.selection, &subject);
+                if names.is_empty() {
+                    return Ok(Some(format!("no {}s", subject.noun())));
+                }
+                let listed: Vec<_> = names
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| format!("{}: {}", i, name))
+                    .collect();
+                Ok(Some(listed.join("  ")))
+            }
+            Line::State => Ok(Some(self.breadcrumb())),
+            Line::Save if self.read_only => Err(self.read_only_error()),
+            Line::Save => {
+                save(self).map_err(AppError::Save)?;
+                self.saved = self.fingerprint();
+                Ok(Some("saved".to_string()))
+            }
+            Line::Undo => {
+                self.undo(false);
+                Ok(None)
+            }
+            Line::Redo => {
+                self.undo(true);
+                Ok(None)
+            }
+            Line::Help => {
+                self.input_state = InputState::Help(0);
+                Ok(None)
+            }
+            Line::Quit { .. } => Ok(None),
+        }
+    }
+
+    // The selection from map down to usage, with the numbers of the selected lineup
+    fn breadcrumb(&self) -> String {
+        let selected = self.progress.selected(&self.selection);
+        let names = [
+            selected.map.map(|m| m.name()),
+            selected.zone.map(|z| z.name()),
+            selected.ability.map(|a| a.name()),
+            selected.usage.map(|u| u.name()),
+        ]
+        .map(|n| n.map_or("-", |n| n.as_str()));
+        let mut crumb = names.join(" › ");
+        if let Ok(key) = self.progress.key(&self.selection) {
+            match self.progress.progress.get(&key) {
+                Some(t) => crumb.push_str(&format!("  {}/{}", t.progress, t.target)),
+                None => crumb.push_str("  untracked"),
+            }
+        }
+        crumb
+    }
+
+    fn change_selected<F>(&mut self, change: F) -> Result<Option<String>, AppError>
+    where
+        F: FnOnce(&mut Target),
+    {
+        self.update_target(change);
+        let key = self.progress.key(&self.selection)?;
+        let t = self
+            .progress
+            .progress
+            .get(&key)
+            .ok_or(AppError::Untracked)?;
+        Ok(Some(format!("{}/{}", t.progress, t.target)))
+    }
+
+    fn start_input(&mut self, op: InputOp, subject: InputSubject) {
+        self.input_state = InputState::edit(op, subject);
+    }
+
+    fn update_target<F: FnOnce(&mut Target)>(&mut self, update: F) {
+        let key = match self.progress.key(&self.selection) {
+            Ok(key) => key,
+            Err(e) => {
+                self.message = Some(Message::error(e.to_string()));
+                return;
+            }
+        };
+        let Some(from) = self.progress.progress.get(&key).cloned() else {
+            self.message = Some(Message::error(AppError::Untracked.to_string()));
+            return;
+        };
+        let mut to = from.clone();
+        update(&mut to);
+        if from != to {
+            if let Err(e) = self.perform(Edit::Target { key, from, to }) {
+                self.message = Some(Message::error(e.to_string()));
+            }
+        }
+    }
+
+    fn start_rename(&mut self, subject: InputSubject) {
+        let selected = self.progress.selected(&self.selection);
+        let current = match subject {
+            InputSubject::Map => selected.map.map(|m| m.name()),
+            InputSubject::Zone => selected.zone.map(|z| z.name()),
+            InputSubject::Ability => selected.ability.map(|a| a.name()),
+            InputSubject::Usage => selected.usage.map(|u| u.name()),
+        };
+        match current {
+            Some(name) => {
+                self.input_state = InputState::edit_with(InputOp::Rename, subject, name.clone())
+            }
+            None => {
+                let text = format!("no {} is selected", subject.noun());
+                self.message = Some(Message::error(text));
+            }
+        }
+    }
+
+    fn completing(&self) -> Option<(&InputSubject, &String)> {
+        match &self.input_state {
+            InputState::Edit((InputOp::Select | InputOp::Remove, subject), buf) => {
+                Some((subject, buf))
+            }
+            _ => None,
+        }
+    }
+
+    fn candidates(&self) -> Vec<String> {
+        let Some((subject, buf)) = self.completing() else {
+            return Vec::new();
+        };
+        let names = subject_names(&self.progress, &self.selection, subject);
+        matches(buf, &names)
+            .into_iter()
+            .map(|i| names[i].to_string())
+            .collect()
+    }
+
+    fn complete(&mut self, forward: bool) {
+        let completion = match self.completion.take() {
+            Some(completion) => completion.cycle(forward),
+            None => {
+                let candidates = self.candidates();
+                if candidates.is_empty() {
+                    return;
+                }
+                let idx = if forward { 0 } else { candidates.len() - 1 };
+                Completion { candidates, idx }
+            }
+        };
+        if let InputState::Edit(_, buf) = &mut self.input_state {
+            *buf = completion.candidates[completion.idx].clone();
+        }
+        self.completion = Some(completion);
+    }
+
+    fn move_selected(&mut self, subject: InputSubject, delta: isize) {
+        // Names keep the moved item selected wherever it ends up
+        self.selection = self
+            .selection
+            .relative(&self.progress.maps, &self.progress.abilities);
+
+        let selected = self.progress.selected(&self.selection);
+        let map = selected.map.map(|m| m.name.clone());
+        let zone = selected.zone.map(|z| z.name.clone());
+        let ability = selected.ability.map(|a| a.name.clone());
+        let usage = selected.usage.map(|u| u.name.clone());
+        let moved = self.rearrange(|store| match (subject, map, zone, ability, usage) {
+            (InputSubject::Map, Some(map), _, _, _) => store.move_map(&map, delta),
+            (InputSubject::Zone, Some(map), Some(zone), _, _) => {
+                store.move_zone(&map, &zone, delta)
+            }
+            (InputSubject::Ability, _, _, Some(ability), _) => store.move_ability(&ability, delta),
+            (InputSubject::Usage, _, _, Some(ability), Some(usage)) => {
+                store.move_usage(&ability, &usage, delta)
+            }
+            _ => Err(ModelError::NoSelection(subject.noun())),
+        });
+        if let Err(e) = moved {
+            self.message = Some(Message::error(e.to_string()));
+        }
+    }
+
+    // Clicks select, clicking the selected lineup again adds progress and right-clicking removes
+    // it, the wheel scrolls or with Ctrl or Alt changes the progress or target under the pointer
+    fn mouse(&mut self, mouse: MouseEvent) {
+        let up = match mouse.kind {
+            MouseEventKind::ScrollUp => true,
+            MouseEventKind::ScrollDown => false,
+            MouseEventKind::Down(button) => {
+                if let InputState::Normal = self.input_state {
+                    self.click(mouse.column, mouse.row, button);
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        let delta = if up { -WHEEL_STEP } else { WHEEL_STEP };
+        match self.input_state {
+            InputState::Help(ref mut scroll) => {
+                *scroll = scroll.saturating_add_signed(delta as i16);
+                return;
+            }
+            InputState::Normal => {}
+            _ => return,
+        }
+        self.message = None;
+        let change = if up { 1 } else { -1 };
+        if mouse.modifiers.contains(KeyModifiers::CONTROL) {
+            self.point_at(mouse.column, mouse.row);
+            self.update_target(|t| t.change_progress(change));
+        } else if mouse.modifiers.contains(KeyModifiers::ALT) {
+            self.point_at(mouse.column, mouse.row);
+            self.update_target(|t| t.change_target(change));
+        } else if mouse.modifiers.contains(KeyModifiers::SHIFT) {
+            self.viewport.scroll_by(0, delta);
+        } else {
+            self.viewport.scroll_by(delta, 0);
+        }
+    }
+
+    fn click(&mut self, x: u16, y: u16, button: MouseButton) {
+        self.message = None;
+        let before = self.progress.key(&self.selection).ok();
+        let Some(hit) = self.point_at(x, y) else {
+            return;
+        };
+        match (button, hit) {
+            (MouseButton::Left, Hit::Lineup(key)) if before == Some(key) => {
+                self.update_target(|t| t.change_progress(1))
+            }
+            (MouseButton::Right, Hit::Lineup(_)) => self.update_target(|t| t.change_progress(-1)),
+            _ => {}
+        }
+    }
+
+    // Selects by name whatever is at the given position, so that it stays selected if moved
+    fn point_at(&mut self, x: u16, y: u16) -> Option<Hit> {
+        let hit = self.viewport.hit(x, y)?;
+        let name = |id| {
+            self.progress
+                .item(id)
+                .map(|item| Selector::Name(item.name().clone()))
+        };
+        match hit {
+            Hit::Lineup((m, z, a, u)) => {
+                self.selection = Selection {
+                    map: name(m),
+                    zone: name(z),
+                    ability: name(a),
+                    usage: name(u),
+                }
+            }
+            Hit::Zone(m, z) => (self.selection.map, self.selection.zone) = (name(m), name(z)),
+            Hit::Usage(a, u) => (self.selection.ability, self.selection.usage) = (name(a), name(u)),
+        }
+        Some(hit)
+    }
+
+    fn render_candidates(&self) -> Spans<'_> {
+        let (candidates, current) = match &self.completion {
+            Some(c) => (c.candidates.clone(), Some(c.idx)),
+            None => (self.candidates(), None),
+        };
+        if candidates.is_empty() {
+            return Spans::from(Span::styled(
+                "no matches",
+                Style::default().add_modifier(Modifier::DIM),
+            ));
+        }
+
+        let mut spans = Vec::new();
+        for (i, candidate) in candidates.into_iter().enumerate() {
+            if i != 0 {
+                spans.push(Span::raw("  "));
+            }
+            let style = match current {
+                Some(idx) if idx == i => Style::default().add_modifier(Modifier::REVERSED),
+                _ => Style::default().add_modifier(Modifier::DIM),
+            };
+            spans.push(Span::styled(candidate, style));
+        }
+        Spans::from(spans)
+    }
+
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let mut rect_constraints = match self.input_state {
+            InputState::Edit(_, _) => {
+                let height = if self.completing().is_some() { 4 } else { 3 };
+                vec![Constraint::Min(0), Constraint::Length(height)]
+            }
+            InputState::Command(_) => vec![Constraint::Min(0), Constraint::Length(3)],
+            InputState::Normal | InputState::Help(_) | InputState::Confirm(_) => {
+                vec![Constraint::Min(0)]
+            }
+        };
+        rect_constraints.push(Constraint::Length(1));
         let rects = Layout::default()
             .margin(1)
             .constraints(rect_constraints)
             .split(f.size());
 
-        let (ncols, mut table) = self.progress.render(&self.selection);
-        let widths;
-        if ncols != 0 {
-            widths = [Constraint::Percentage(100 / ncols as u16)].repeat(ncols);
-            table = table.widths(&widths);
-        }
-        f.render_stateful_widget(table, rects[0], &mut self.state);
+        let (widths, table) = self
+            .progress
+            .render((&self.selection, &mut self.viewport, rects[0]));
+        f.render_widget(table.widths(&widths), rects[0]);
 
         if let InputState::Edit(t, s) = &self.input_state {
             let mut box_name = match t {
+                (InputOp::Sort, InputSubject::Map | InputSubject::Zone) => "Sort rows by ",
+                (InputOp::Sort, InputSubject::Ability | InputSubject::Usage) => "Sort columns by ",
                 (InputOp::New, _) => "New ",
                 (InputOp::Select, _) => "Select ",
                 (InputOp::Remove, _) => "Remove ",
+                (InputOp::Rename, _) => "Rename ",
             }
             .to_string();
             box_name.push_str(match t {
-                (_, InputSubject::MapName) => "Map",
-                (_, InputSubject::ZoneName) => "Zone",
-                (_, InputSubject::AbilityName) => "Ability",
-                (_, InputSubject::UsageName) => "Usage",
+                (InputOp::Sort, _) => "name, completion or recent (prefix - to reverse)",
+                (_, InputSubject::Map) => "Map",
+                (_, InputSubject::Zone) => "Zone",
+                (_, InputSubject::Ability) => "Ability",
+                (_, InputSubject::Usage) => "Usage",
             });
-            let input_box = Paragraph::new(s.as_ref())
-                .block(Block::default().borders(Borders::ALL).title(box_name));
+            if let Some(Message {
+                text,
+                is_error: true,
+            }) = &self.message
+            {
+                box_name.push_str(": ");
+                box_name.push_str(text);
+            }
+            let mut lines = vec![Spans::from(s.as_str())];
+            if self.completing().is_some() {
+                lines.push(self.render_candidates());
+            }
+            let input_box =
+                Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(box_name));
             f.render_widget(input_box, rects[1]);
             f.set_cursor(rects[1].x + s.width() as u16 + 1, rects[1].y + 1)
         }
+
+        if let InputState::Command(s) = &self.input_state {
+            let command_box = Paragraph::new(s.as_str()).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Command (Up/Down for history, see ? for commands)"),
+            );
+            f.render_widget(command_box, rects[1]);
+            f.set_cursor(rects[1].x + s.width() as u16 + 1, rects[1].y + 1)
+        }
+
+        // Errors while editing are shown on the input box instead
+        let crumb = self.breadcrumb();
+        let mut status = Vec::new();
+        if self.read_only {
+            status.push(Span::styled(
+                "read-only",
+                Style::default().fg(Color::Yellow),
+            ));
+            status.push(Span::raw("  "));
+        }
+        status.push(Span::styled(
+            crumb.as_str(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+        match (&self.input_state, &self.message) {
+            (InputState::Edit(_, _), Some(Message { is_error: true, .. })) | (_, None) => {}
+            (_, Some(message)) => {
+                let style = if message.is_error {
+                    Style::default().fg(Color::Red)
+                } else {
+                    Style::default()
+                };
+                status.push(Span::raw("  "));
+                status.push(Span::styled(message.text.as_str(), style));
+            }
+        }
+        f.render_widget(Paragraph::new(Spans::from(status)), rects[rects.len() - 1]);
+
+        if let InputState::Confirm(removal) = &self.input_state {
+            let lines = vec![
+                Spans::from(removal.losses()),
+                Spans::default(),
+                Spans::from(Span::styled(
+                    "y/Enter to remove, n/Esc to keep",
+                    Style::default().add_modifier(Modifier::DIM),
+                )),
+            ];
+            let title = format!("Remove {}?", removal.what());
+            let width = lines
+                .iter()
+                .map(|l| l.width())
+                .chain([title.width()])
+                .max()
+                .unwrap_or_default() as u16;
+            let area = sized(f.size(), width + 4, lines.len() as u16 + 2);
+            let popup = Paragraph::new(lines).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(Span::styled(title, Style::default().fg(Color::Red))),
+            );
+            f.render_widget(Clear, area);
+            f.render_widget(popup, area);
+        }
+
+        let help = self.help_lines();
+        if let InputState::Help(scroll) = &mut self.input_state {
+            let area = centred(f.size(), 70, 80);
+            let visible = area.height.saturating_sub(2);
+            *scroll = (*scroll).min((help.len() as u16).saturating_sub(visible));
+            let popup = Paragraph::new(help)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Keys (Esc to close, j/k to scroll)"),
+                )
+                .scroll((*scroll, 0));
+            f.render_widget(Clear, area);
+            f.render_widget(popup, area);
+        }
     }
+
+    fn help_lines(&self) -> Vec<Spans<'static>> {
+        let actions = self.keys.actions();
+        let mut lines = Vec::new();
+        for category in CATEGORIES {
+            if !lines.is_empty() {
+                lines.push(Spans::default());
+            }
+            lines.push(Spans::from(Span::styled(
+                category.title(),
+                Style::default().add_modifier(Modifier::BOLD),
+            )));
+            for action in actions.iter().filter(|a| a.category() == category) {
+                let keys: Vec<_> = self
+                    .keys
+                    .keys_for(*action)
+                    .iter()
+                    .map(|k| k.to_string())
+                    .collect();
+                lines.push(Spans::from(vec![
+                    Span::raw(format!(
+                        "  {:<16} {:<40}",
+                        keys.join(", "),
+                        action.description()
+                    )),
+                    Span::styled(
+                        action.to_string(),
+                        Style::default().add_modifier(Modifier::DIM),
+                    ),
+                ]));
+            }
+        }
+
+        lines.push(Spans::default());
+        lines.push(Spans::from(Span::styled(
+            "Mouse",
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        for (what, description) in MOUSE_HELP {
+            lines.push(Spans::from(format!("  {:<16} {}", what, description)));
+        }
+
+        lines.push(Spans::default());
+        lines.push(Spans::from(Span::styled(
+            "Commands (may be abbreviated)",
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        for (syntax, description) in command_line::SYNTAX {
+            lines.push(Spans::from(format!("  {:<40} {}", syntax, description)));
+        }
+        lines
+    }
+}
+
+fn centred(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    sized(
+        area,
+        area.width * percent_x / 100,
+        area.height * percent_y / 100,
+    )
+}
+
+fn sized(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
 }
 
 impl From<SaveState> for App {
     fn from(s: SaveState) -> Self {
         App {
-            state: TableState::default(),
             progress: s.progress,
-            input_state: InputState::Normal,
             selection: s.selection,
+            ..App::new(String::new())
         }
     }
 }
 
-impl Into<SaveState> for App {
-    fn into(self) -> SaveState {
-        SaveState {
-            progress: self.progress,
-            selection: self.selection,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::test_store;
+
+    #[test]
+    fn removals_need_whole_names_or_prefixes() {
+        let mut app = App::new(String::new());
+        app.progress = test_store();
+        app.confirm_removals = false;
+        let mut save = |_: &App| Ok(());
+
+        assert_eq!(
+            app.submit(InputOp::Remove, InputSubject::Map, "v")
+                .unwrap_err()
+                .to_string(),
+            "no map named 'v', give the whole name of 'Haven' or a prefix of it"
+        );
+        assert!(matches!(
+            app.submit(InputOp::Remove, InputSubject::Map, "9"),
+            Err(AppError::Model(ModelError::OutOfRange("map", 9)))
+        ));
+        app.execute("remove zone hav grg", &mut save);
+        assert_eq!(
+            app.message.take().unwrap().text,
+            "no zone named 'grg', give the whole name of 'Garage' or a prefix of it"
+        );
+        app.execute("remove zone hav ga", &mut save);
+        assert_eq!(app.message.take().unwrap().text, "removed zone 'Garage'");
+        app.execute("rename map h Bind", &mut save);
+        assert_eq!(app.progress.maps[0].name, "Bind");
+        assert!(app.submit(InputOp::Remove, InputSubject::Map, "0").is_ok());
+        assert!(app.progress.maps.is_empty());
+    }
+
+    #[test]
+    fn reloads_keep_the_selection_and_count_conflicts() {
+        let mut app = App::new("Progress".into());
+        app.progress.add_map("Haven".into()).unwrap();
+        for zone in ["Garage", "Hookah"] {
+            app.progress
+                .add_zone(&Selector::Index(0), zone.into())
+                .unwrap();
+        }
+        app.selection = Selection {
+            map: Some(Selector::Index(0)),
+            zone: Some(Selector::Index(1)),
+            ..Selection::default()
+        };
+        app.saved = app.fingerprint();
+
+        let changed = AtomicBool::new(false);
+        let mut reload = |progress: &mut ProgressStore| {
+            progress.maps[0].zones.reverse();
+            Ok(Some(1))
+        };
+        app.reload(&changed, &mut reload);
+        assert!(app.message.is_none());
+
+        changed.store(true, Ordering::Relaxed);
+        app.reload(&changed, &mut reload);
+        let zone = app.selection.zone.as_ref().unwrap();
+        assert_eq!(
+            zone.get_selected(&app.progress.maps[0].zones).unwrap().name,
+            "Hookah"
+        );
+        // Nothing was changed here, so there is still nothing to save
+        assert_eq!(app.saved, app.fingerprint());
+        assert_eq!(
+            app.message.unwrap().text,
+            "reloaded changes made elsewhere; 1 lineup changed in both kept this progress"
+        );
+        assert!(!changed.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn reloads_forget_edits_to_what_was_removed() {
+        let mut app = App::new(String::new());
+        app.progress = test_store();
+        app.selection = Selection::default();
+        app.change_selected(|t| t.change_progress(1)).unwrap();
+        let key = app.progress.key(&app.selection).unwrap();
+
+        let changed = AtomicBool::new(true);
+        app.reload(&changed, &mut |progress: &mut ProgressStore| {
+            progress.rm_zone("Haven", "A Main")?;
+            Ok(Some(0))
+        });
+        app.undo(false);
+        assert_eq!(app.message.unwrap().text, "nothing to undo");
+        assert!(!app.progress.progress.contains_key(&key));
+        assert_eq!(
+            app.progress.set_target_of(key, Target::default()),
+            Err(ModelError::UnknownId(key.1))
+        );
+    }
+
+    #[test]
+    fn clicks_select_what_they_hit() {
+        let mut app = App::new(String::new());
+        app.progress = test_store();
+        app.selection = Selection::default();
+        let area = Rect::new(0, 0, 40, 10);
+        app.progress
+            .render((&app.selection, &mut app.viewport, area));
+        let progress = |app: &App| -> Vec<i32> {
+            app.progress.progress.values().map(|t| t.progress).collect()
+        };
+        let selected = |app: &App| {
+            let s = app.progress.selected(&app.selection);
+            [
+                s.map.map(|m| m.name.as_str()),
+                s.zone.map(|z| z.name.as_str()),
+                s.ability.map(|a| a.name.as_str()),
+                s.usage.map(|u| u.name.as_str()),
+            ]
+            .map(|name| name.unwrap_or("-"))
+            .join(" ")
+        };
+
+        // The first click on a lineup selects it, and the next counts a practice
+        app.click(22, 4, MouseButton::Left);
+        assert_eq!(selected(&app), "Haven Garage Smoke Default");
+        assert!(matches!(app.selection.zone, Some(Selector::Name(_))));
+        assert_eq!(progress(&app), vec![0, 1, 2, 3]);
+        app.click(22, 4, MouseButton::Left);
+        assert_eq!(progress(&app), vec![0, 1, 2, 4]);
+        app.click(14, 4, MouseButton::Right);
+        assert_eq!(selected(&app), "Haven Garage Smoke One-way");
+        assert_eq!(progress(&app), vec![0, 1, 1, 4]);
+
+        // Names and headers select only their own axis
+        app.click(7, 3, MouseButton::Left);
+        assert_eq!(selected(&app), "Haven A Main Smoke One-way");
+        app.click(29, 2, MouseButton::Left);
+        assert_eq!(selected(&app), "Haven A Main Smoke Default");
+
+        // Clicks elsewhere change nothing
+        for (x, y) in [(0, 0), (3, 1), (14, 5), (30, 3), (39, 9)] {
+            app.click(x, y, MouseButton::Left);
+            assert_eq!(selected(&app), "Haven A Main Smoke Default");
         }
+        assert_eq!(progress(&app), vec![0, 1, 1, 4]);
     }
 }
@@ -0,0 +1,784 @@
+use crate::model::{check_name, Nameable, ProgressStore, Target};
+use crate::selection::{Resolved, Selector};
+use crate::spreadsheet;
+use std::fs::{self, File};
+use std::io::{stdin, BufReader, Error, ErrorKind, Result};
+
+pub const USAGE: &str = "\
+usage: lineup-tracker [<options>] [<command> <args>...]
+
+With no command, the interactive tracker is opened.
+
+Options:
+    -f, --file <path>       Use the save file at <path>
+    -p, --profile <name>    Use the save file of the named profile
+    --backups <n>           Keep <n> backups of the save file (default 5)
+    --restore-backup [n]    List backups of the save file, or restore backup <n>
+
+The save file may also be set with $LINEUP_TRACKER_FILE and the profile
+with $LINEUP_TRACKER_PROFILE, though -f and -p override both. By default, progress is saved in
+$XDG_DATA_HOME/lineup-tracker/progress.json and profiles are saved in
+$XDG_DATA_HOME/lineup-tracker/profiles/. A backup is taken when saving over
+the file, at most once an hour.
+
+Settings are read from $XDG_CONFIG_HOME/lineup-tracker/config.json, or the
+file named by $LINEUP_TRACKER_CONFIG. Keys are rebound by action name, as in
+    { \"keys\": { \"next-zone\": [\"n\", \"Down\"], \"select-map\": [\"Ctrl-a\"] } }
+where rebinding an action replaces all of its default keys. Action names are
+listed by pressing ? in the interactive tracker. Removals ask for confirmation
+unless \"confirm-removals\" is set to false, and names which differ only in
+case count as duplicates if \"case-insensitive-names\" is set to true. Changes
+are saved every \"autosave-interval\" seconds if it is set, in which case
+quitting without saving only discards changes made since the last autosave.
+
+Profile commands:
+    profiles                                         List known profiles
+
+Progress commands:
+    incr <map> <zone> <ability> <usage> [n]          Add n reps of progress (default 1)
+    decr <map> <zone> <ability> <usage> [n]          Remove n reps of progress (default 1)
+    set-progress <map> <zone> <ability> <usage> <n>  Set progress to n
+    incr-target <map> <zone> <ability> <usage> [n]   Raise the target by n (default 1)
+    decr-target <map> <zone> <ability> <usage> [n]   Lower the target by n (default 1)
+    set-target <map> <zone> <ability> <usage> <n>    Set the target to n
+
+Structure commands:
+    add-map <name>
+    add-zone <map> <name>
+    add-ability <name>
+    add-usage <ability> <usage>
+    rm-map <map>
+    rm-zone <map> <zone>
+    rm-ability <ability>
+    rm-usage <ability> <usage>
+    rename-map <map> <name>
+    rename-zone <map> <zone> <name>
+    rename-ability <ability> <name>
+    rename-usage <ability> <usage> <name>
+
+Spreadsheet commands:
+    export [--grid] [<file>]                         Write progress as CSV, by default to stdout
+    import [--dry-run] <file>                        Read progress from CSV, or stdin if <file> is -
+
+Exports have a row for each lineup, or with --grid are laid out as in the
+tracker. Imports accept either layout, add any maps, zones, abilities and
+usages which are missing and never remove anything. With --dry-run, the
+changes an import would make are listed but not saved.
+
+Maps, zones, abilities and usages may be given by index, or by name or any
+unambiguous prefix or abbreviation of it. Removals and renames only accept a
+name or an unambiguous prefix of it.
+";
+
+pub enum Change {
+    By(i32),
+    To(i32),
+}
+
+pub struct TargetPath {
+    map: Selector,
+    zone: Selector,
+    ability: Selector,
+    usage: Selector,
+}
+
+pub struct Options {
+    pub file: Option<String>,
+    pub profile: Option<String>,
+    pub backups: Option<usize>,
+    pub restore_backup: Option<Option<usize>>,
+    pub command: Option<Command>,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut file = None;
+        let mut profile = None;
+        let mut backups = None;
+        let mut restore_backup = None;
+
+        let mut args = args;
+        while let Some((arg, rest)) = args.split_first() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            if flag == "--restore-backup" {
+                let (idx, rest) = match (inline_value, rest.split_first()) {
+                    (Some(value), _) => (Some(Self::index(&value)?), rest),
+                    (None, Some((value, tail))) if value.parse::<usize>().is_ok() => {
+                        (Some(Self::index(value)?), tail)
+                    }
+                    (None, _) => (None, rest),
+                };
+                restore_backup = Some(idx);
+                args = rest;
+                continue;
+            }
+
+            let mut count = None;
+            let dest = match flag {
+                "-f" | "--file" => &mut file,
+                "-p" | "--profile" => &mut profile,
+                "--backups" => &mut count,
+                "--" => {
+                    args = rest;
+                    break;
+                }
+                _ if flag.starts_with('-') && !Self::is_help(flag) => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("unknown option '{}', try 'help'", flag),
+                    ))
+                }
+                _ => break,
+            };
+            let (value, rest) = match (inline_value, rest.split_first()) {
+                (Some(value), _) => (value, rest),
+                (None, Some((value, rest))) => (value.clone(), rest),
+                (None, None) => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("option '{}' requires a value", flag),
+                    ))
+                }
+            };
+            *dest = Some(value);
+            if let Some(count) = count {
+                backups = Some(Self::index(&count)?);
+            }
+            args = rest;
+        }
+
+        Ok(Self {
+            file,
+            profile,
+            backups,
+            restore_backup,
+            command: Command::parse(args)?,
+        })
+    }
+
+    fn index(n: &str) -> Result<usize> {
+        n.parse().map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("expected a non-negative number, got '{}'", n),
+            )
+        })
+    }
+
+    fn is_help(flag: &str) -> bool {
+        flag == "-h" || flag == "--help"
+    }
+}
+
+pub enum Command {
+    Help,
+    Profiles,
+    Progress(TargetPath, Change),
+    Target(TargetPath, Change),
+    AddMap(String),
+    AddZone(Selector, String),
+    AddAbility(String),
+    AddUsage(Selector, String),
+    RmMap(Selector),
+    RmZone(Selector, Selector),
+    RmAbility(Selector),
+    RmUsage(Selector, Selector),
+    RenameMap(Selector, String),
+    RenameZone(Selector, Selector, String),
+    RenameAbility(Selector, String),
+    RenameUsage(Selector, Selector, String),
+    Export { grid: bool, file: Option<String> },
+    Import { dry_run: bool, file: String },
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Option<Self>> {
+        let (cmd, args) = match args.split_first() {
+            Some((cmd, args)) => (cmd.as_str(), args),
+            None => return Ok(None),
+        };
+
+        let cmd = match cmd {
+            "help" | "-h" | "--help" => Command::Help,
+            "profiles" => Command::Profiles,
+            "incr" => {
+                let (path, n) = Self::path_and_count(cmd, args, Some(1))?;
+                Command::Progress(path, Change::By(n))
+            }
+            "decr" => {
+                let (path, n) = Self::path_and_count(cmd, args, Some(1))?;
+                Command::Progress(path, Change::By(-n))
+            }
+            "set-progress" => {
+                let (path, n) = Self::path_and_count(cmd, args, None)?;
+                Command::Progress(path, Change::To(n))
+            }
+            "incr-target" => {
+                let (path, n) = Self::path_and_count(cmd, args, Some(1))?;
+                Command::Target(path, Change::By(n))
+            }
+            "decr-target" => {
+                let (path, n) = Self::path_and_count(cmd, args, Some(1))?;
+                Command::Target(path, Change::By(-n))
+            }
+            "set-target" => {
+                let (path, n) = Self::path_and_count(cmd, args, None)?;
+                Command::Target(path, Change::To(n))
+            }
+            "add-map" => match args {
+                [name] => Command::AddMap(Self::new_name(name)?),
+                _ => return Err(Self::arity(cmd, "<name>")),
+            },
+            "add-zone" => match args {
+                [map, name] => Command::AddZone(map.clone().into(), Self::new_name(name)?),
+                _ => return Err(Self::arity(cmd, "<map> <name>")),
+            },
+            "add-ability" => match args {
+                [name] => Command::AddAbility(Self::new_name(name)?),
+                _ => return Err(Self::arity(cmd, "<name>")),
+            },
+            "add-usage" => match args {
+                [ability, name] => Command::AddUsage(ability.clone().into(), Self::new_name(name)?),
+                _ => return Err(Self::arity(cmd, "<ability> <name>")),
+            },
+            "rm-map" => match args {
+                [map] => Command::RmMap(map.clone().into()),
+                _ => return Err(Self::arity(cmd, "<map>")),
+            },
+            "rm-zone" => match args {
+                [map, zone] => Command::RmZone(map.clone().into(), zone.clone().into()),
+                _ => return Err(Self::arity(cmd, "<map> <zone>")),
+            },
+            "rm-ability" => match args {
+                [ability] => Command::RmAbility(ability.clone().into()),
+                _ => return Err(Self::arity(cmd, "<ability>")),
+            },
+            "rm-usage" => match args {
+                [ability, usage] => Command::RmUsage(ability.clone().into(), usage.clone().into()),
+                _ => return Err(Self::arity(cmd, "<ability> <usage>")),
+            },
+            "rename-map" => match args {
+                [map, name] => Command::RenameMap(map.clone().into(), Self::new_name(name)?),
+                _ => return Err(Self::arity(cmd, "<map> <name>")),
+            },
+            "rename-zone" => match args {
+                [map, zone, name] => Command::RenameZone(
+                    map.clone().into(),
+                    zone.clone().into(),
+                    Self::new_name(name)?,
+                ),
+                _ => return Err(Self::arity(cmd, "<map> <zone> <name>")),
+            },
+            "rename-ability" => match args {
+                [ability, name] => {
+                    Command::RenameAbility(ability.clone().into(), Self::new_name(name)?)
+                }
+                _ => return Err(Self::arity(cmd, "<ability> <name>")),
+            },
+            "rename-usage" => match args {
+                [ability, usage, name] => Command::RenameUsage(
+                    ability.clone().into(),
+                    usage.clone().into(),
+                    Self::new_name(name)?,
+                ),
+                _ => return Err(Self::arity(cmd, "<ability> <usage> <name>")),
+            },
+            "export" => {
+                let (grid, rest) = Self::flag(args, "--grid");
+                match rest.as_slice() {
+                    [] => Command::Export { grid, file: None },
+                    [file] => Command::Export {
+                        grid,
+                        file: Some(file.to_string()),
+                    },
+                    _ => return Err(Self::arity(cmd, "[--grid] [<file>]")),
+                }
+            }
+            "import" => {
+                let (dry_run, rest) = Self::flag(args, "--dry-run");
+                match rest.as_slice() {
+                    [file] => Command::Import {
+                        dry_run,
+                        file: file.to_string(),
+                    },
+                    _ => return Err(Self::arity(cmd, "[--dry-run] <file>")),
+                }
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("unknown command '{}', try 'help'", cmd),
+                ))
+            }
+        };
+        Ok(Some(cmd))
+    }
+
+    // Whether the store should be saved after this command is applied
+    pub fn modifies(&self) -> bool {
+        !matches!(
+            self,
+            Command::Export { .. } | Command::Import { dry_run: true, .. }
+        )
+    }
+
+    fn flag<'a>(args: &'a [String], flag: &str) -> (bool, Vec<&'a str>) {
+        let rest: Vec<_> = args
+            .iter()
+            .map(String::as_str)
+            .filter(|a| *a != flag)
+            .collect();
+        (rest.len() < args.len(), rest)
+    }
+
+    fn path_and_count(
+        cmd: &str,
+        args: &[String],
+        default: Option<i32>,
+    ) -> Result<(TargetPath, i32)> {
+        let count_usage = if default.is_some() { "[n]" } else { "<n>" };
+        let (path, n) = match (args, default) {
+            ([m, z, a, u], Some(n)) => ([m, z, a, u], n),
+            ([m, z, a, u, n], _) => ([m, z, a, u], Self::count(n)?),
+            _ => {
+                return Err(Self::arity(
+                    cmd,
+                    &format!("<map> <zone> <ability> <usage> {}", count_usage),
+                ))
+            }
+        };
+        let [m, z, a, u] = path.map(|s| Selector::from(s.clone()));
+        Ok((
+            TargetPath {
+                map: m,
+                zone: z,
+                ability: a,
+                usage: u,
+            },
+            n,
+        ))
+    }
+
+    // Negative counts would turn incr into decr, and cannot all be negated
+    fn count(n: &str) -> Result<i32> {
+        n.parse().ok().filter(|n| *n >= 0).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("expected a non-negative number, got '{}'", n),
+            )
+        })
+    }
+
+    fn new_name(name: &str) -> Result<String> {
+        check_name(name)?;
+        Ok(name.to_string())
+    }
+
+    fn arity(cmd: &str, args: &str) -> Error {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("usage: lineup-tracker {} {}", cmd, args),
+        )
+    }
+
+    pub fn apply(self, store: &mut ProgressStore) -> Result<Option<String>> {
+        match self {
+            // These do not touch the store and are handled before it is loaded
+            Command::Help | Command::Profiles => {}
+            Command::Progress(path, change) => {
+                let t = Self::target_mut(store, &path)?;
+                match change {
+                    Change::By(n) => t.change_progress(n),
+                    Change::To(n) => t.set_progress(n),
+                }
+                return Ok(Some(format!("{}/{}", t.progress, t.target)));
+            }
+            Command::Target(path, change) => {
+                let t = Self::target_mut(store, &path)?;
+                match change {
+                    Change::By(n) => t.change_target(n),
+                    Change::To(n) => t.set_target(n),
+                }
+                return Ok(Some(format!("{}/{}", t.progress, t.target)));
+            }
+            Command::AddMap(name) => {
+                store.add_map(name)?;
+            }
+            Command::AddZone(msel, name) => {
+                let map = Selector::Name(Self::resolve(&msel, &store.maps, "map")?.name().clone());
+                store.add_zone(&map, name)?;
+            }
+            Command::AddAbility(name) => {
+                store.add_ability(name)?;
+            }
+            Command::AddUsage(asel, name) => {
+                let ability = Self::resolve(&asel, &store.abilities, "ability")?;
+                let ability = Selector::Name(ability.name().clone());
+                store.add_usage(&ability, name)?;
+            }
+            Command::RmMap(msel) => {
+                let map = Self::resolve_strictly(&msel, &store.maps, "map")?
+                    .name()
+                    .clone();
+                store.rm_map(&map)?;
+                return Ok(Some(format!("removed map '{}'", map)));
+            }
+            Command::RmZone(msel, zsel) => {
+                let map = Self::resolve_strictly(&msel, &store.maps, "map")?;
+                let zone = Self::resolve_strictly(&zsel, &map.zones, "zone")?
+                    .name()
+                    .clone();
+                let map = map.name().clone();
+                store.rm_zone(&map, &zone)?;
+                return Ok(Some(format!("removed zone '{}' from '{}'", zone, map)));
+            }
+            Command::RmAbility(asel) => {
+                let ability = Self::resolve_strictly(&asel, &store.abilities, "ability")?
+                    .name()
+                    .clone();
+                store.rm_ability(&ability)?;
+                return Ok(Some(format!("removed ability '{}'", ability)));
+            }
+            Command::RmUsage(asel, usel) => {
+                let ability = Self::resolve_strictly(&asel, &store.abilities, "ability")?;
+                let usage = Self::resolve_strictly(&usel, &ability.usages, "usage")?
+                    .name()
+                    .clone();
+                let ability = ability.name().clone();
+                store.rm_usage(&ability, &usage)?;
+                return Ok(Some(format!(
+                    "removed usage '{}' from '{}'",
+                    usage, ability
+                )));
+            }
+            Command::RenameMap(msel, name) => {
+                let map = Self::resolve_strictly(&msel, &store.maps, "map")?
+                    .name()
+                    .clone();
+                store.rename_map(&map, name.clone())?;
+                return Ok(Some(format!("renamed map '{}' to '{}'", map, name)));
+            }
+            Command::RenameZone(msel, zsel, name) => {
+                let map = Self::resolve_strictly(&msel, &store.maps, "map")?;
+                let zone = Self::resolve_strictly(&zsel, &map.zones, "zone")?
+                    .name()
+                    .clone();
+                let map = map.name().clone();
+                store.rename_zone(&map, &zone, name.clone())?;
+                return Ok(Some(format!("renamed zone '{}' to '{}'", zone, name)));
+            }
+            Command::RenameAbility(asel, name) => {
+                let ability = Self::resolve_strictly(&asel, &store.abilities, "ability")?
+                    .name()
+                    .clone();
+                store.rename_ability(&ability, name.clone())?;
+                return Ok(Some(format!("renamed ability '{}' to '{}'", ability, name)));
+            }
+            Command::RenameUsage(asel, usel, name) => {
+                let ability = Self::resolve_strictly(&asel, &store.abilities, "ability")?;
+                let usage = Self::resolve_strictly(&usel, &ability.usages, "usage")?
+                    .name()
+                    .clone();
+                let ability = ability.name().clone();
+                store.rename_usage(&ability, &usage, name.clone())?;
+                return Ok(Some(format!("renamed usage '{}' to '{}'", usage, name)));
+            }
+            Command::Export { grid, file } => {
+                let export = match grid {
+                    true => spreadsheet::export_grid,
+                    false => spreadsheet::export_long,
+                };
+                let mut out = Vec::new();
+                export(store, &mut out)?;
+                match file {
+                    Some(file) => fs::write(file, out)?,
+                    None => return Ok(Some(String::from_utf8_lossy(&out).trim_end().to_string())),
+                }
+            }
+            Command::Import { dry_run, file } => {
+                let changes = match file.as_str() {
+                    "-" => spreadsheet::import(store, stdin().lock())?,
+                    file => spreadsheet::import(store, BufReader::new(File::open(file)?))?,
+                };
+                let summary = match (changes.len(), dry_run) {
+                    (0, _) => "Nothing to import".to_string(),
+                    (n, true) => format!(
+                        "Would make {} (dry run, nothing was saved)",
+                        Self::changes(n)
+                    ),
+                    (n, false) => format!("Made {}", Self::changes(n)),
+                };
+                let mut out = changes;
+                out.push(summary);
+                return Ok(Some(out.join("\n")));
+            }
+        }
+        Ok(None)
+    }
+
+    fn changes(n: usize) -> String {
+        format!("{} change{}", n, if n == 1 { "" } else { "s" })
+    }
+
+    fn target_mut<'a>(store: &'a mut ProgressStore, path: &TargetPath) -> Result<&'a mut Target> {
+        let map = Self::resolve(&path.map, &store.maps, "map")?;
+        let zone = Self::resolve(&path.zone, &map.zones, "zone")?;
+        let ability = Self::resolve(&path.ability, &store.abilities, "ability")?;
+        let usage = Self::resolve(&path.usage, &ability.usages, "usage")?;
+        let key = (map.id, zone.id, ability.id, usage.id);
+        let usage = usage.name().clone();
+        store.progress.get_mut(&key).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("no progress is tracked for {}", usage),
+            )
+        })
+    }
+
+    fn resolve<'a, S: Nameable>(sel: &Selector, vs: &'a [S], what: &str) -> Result<&'a S> {
+        Self::found(sel.resolve(vs), sel, vs, what)
+    }
+
+    // Removals and renames only take whole names or prefixes, as a loose match may be a typo
+    fn resolve_strictly<'a, S: Nameable>(sel: &Selector, vs: &'a [S], what: &str) -> Result<&'a S> {
+        match (sel.resolve_strictly(vs), sel.resolve(vs), sel) {
+            (Resolved::Missing, Resolved::Found(i), Selector::Name(name)) => Err(Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "no {} named '{}', give the whole name of '{}' or a prefix of it",
+                    what,
+                    name,
+                    vs[i].name()
+                ),
+            )),
+            (resolved, _, _) => Self::found(resolved, sel, vs, what),
+        }
+    }
+
+    fn found<'a, S: Nameable>(
+        resolved: Resolved,
+        sel: &Selector,
+        vs: &'a [S],
+        what: &str,
+    ) -> Result<&'a S> {
+        match (resolved, sel) {
+            (Resolved::Found(i), _) => Ok(&vs[i]),
+            (Resolved::Ambiguous(is), Selector::Name(name)) => {
+                let names: Vec<_> = is.iter().map(|i| vs[*i].name().as_str()).collect();
+                Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "ambiguous {} '{}', could be any of: {}",
+                        what,
+                        name,
+                        names.join(", ")
+                    ),
+                ))
+            }
+            _ => Err(Self::not_found(sel, what)),
+        }
+    }
+
+    fn not_found(sel: &Selector, what: &str) -> Error {
+        let msg = match sel {
+            Selector::Name(name) => format!("no {} named '{}'", what, name),
+            Selector::Index(idx) => format!("no {} at index {}", what, idx),
+        };
+        Error::new(ErrorKind::NotFound, msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::test_store;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn run(store: &mut ProgressStore, line: &str) -> Result<Option<String>> {
+        Command::parse(&args(line))?.unwrap().apply(store)
+    }
+
+    #[test]
+    fn removals_need_whole_names_or_prefixes() {
+        let mut store = test_store();
+        assert_eq!(
+            run(&mut store, "rm-map v").unwrap_err().to_string(),
+            "no map named 'v', give the whole name of 'Haven' or a prefix of it"
+        );
+        assert_eq!(
+            run(&mut store, "rename-zone hav gr Hookah")
+                .unwrap_err()
+                .to_string(),
+            "no zone named 'gr', give the whole name of 'Garage' or a prefix of it"
+        );
+        assert_eq!(
+            run(&mut store, "rename-zone hav ga Hookah").unwrap(),
+            Some("renamed zone 'Garage' to 'Hookah'".to_string())
+        );
+        assert_eq!(
+            run(&mut store, "rm-usage Smoke d").unwrap(),
+            Some("removed usage 'Default' from 'Smoke'".to_string())
+        );
+        assert_eq!(store.abilities[0].usages.len(), 1);
+    }
+
+    fn error(line: &str) -> String {
+        match Command::parse(&args(line)) {
+            Ok(_) => panic!("'{}' parsed", line),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    fn options(line: &str) -> Options {
+        Options::parse(&args(line)).unwrap()
+    }
+
+    #[test]
+    fn commands_take_their_arguments() {
+        let usages = [
+            ("incr", "<map> <zone> <ability> <usage> [n]"),
+            ("decr", "<map> <zone> <ability> <usage> [n]"),
+            ("set-progress", "<map> <zone> <ability> <usage> <n>"),
+            ("incr-target", "<map> <zone> <ability> <usage> [n]"),
+            ("decr-target", "<map> <zone> <ability> <usage> [n]"),
+            ("set-target", "<map> <zone> <ability> <usage> <n>"),
+            ("add-map", "<name>"),
+            ("add-zone", "<map> <name>"),
+            ("add-ability", "<name>"),
+            ("add-usage", "<ability> <name>"),
+            ("rm-map", "<map>"),
+            ("rm-zone", "<map> <zone>"),
+            ("rm-ability", "<ability>"),
+            ("rm-usage", "<ability> <usage>"),
+            ("rename-map", "<map> <name>"),
+            ("rename-zone", "<map> <zone> <name>"),
+            ("rename-ability", "<ability> <name>"),
+            ("rename-usage", "<ability> <usage> <name>"),
+            ("import", "[--dry-run] <file>"),
+        ];
+        for (cmd, usage) in usages {
+            let expected = format!("usage: lineup-tracker {} {}", cmd, usage);
+            let wanted = usage.split(' ').filter(|a| a.starts_with('<')).count();
+            for given in [wanted.saturating_sub(1), wanted + 2] {
+                let line = format!("{} {}", cmd, vec!["1"; given].join(" "));
+                assert_eq!(error(&line), expected, "{}", line);
+            }
+        }
+        assert_eq!(
+            error("export a b"),
+            "usage: lineup-tracker export [--grid] [<file>]"
+        );
+        assert_eq!(
+            error("frobnicate"),
+            "unknown command 'frobnicate', try 'help'"
+        );
+
+        let parsed = |line: &str| Command::parse(&args(line)).unwrap().unwrap();
+        assert!(matches!(
+            parsed("incr h a s o"),
+            Command::Progress(_, Change::By(1))
+        ));
+        assert!(matches!(
+            parsed("decr-target h a s o 3"),
+            Command::Target(_, Change::By(-3))
+        ));
+        assert!(matches!(
+            parsed("set-progress h a s o 0"),
+            Command::Progress(_, Change::To(0))
+        ));
+        assert!(matches!(
+            parsed("export --grid"),
+            Command::Export {
+                grid: true,
+                file: None
+            }
+        ));
+        assert!(matches!(
+            parsed("import x.csv --dry-run"),
+            Command::Import { dry_run: true, .. }
+        ));
+        assert!(Command::parse(&[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn counts_must_be_non_negative_numbers() {
+        for n in ["x", "1.5", "-1", "2147483648"] {
+            assert_eq!(
+                error(&format!("set-target h a s o {}", n)),
+                format!("expected a non-negative number, got '{}'", n)
+            );
+        }
+        assert_eq!(
+            Options::parse(&args("--backups -1"))
+                .err()
+                .unwrap()
+                .to_string(),
+            "expected a non-negative number, got '-1'"
+        );
+    }
+
+    #[test]
+    fn options_come_before_the_command() {
+        let opts = options("-f a.json --profile=work --backups 3 incr h a s o");
+        assert_eq!(opts.file.as_deref(), Some("a.json"));
+        assert_eq!(opts.profile.as_deref(), Some("work"));
+        assert_eq!(opts.backups, Some(3));
+        assert!(matches!(opts.command, Some(Command::Progress(..))));
+
+        let opts = options("--file=b.json -p work");
+        assert_eq!(opts.file.as_deref(), Some("b.json"));
+        assert_eq!(opts.profile.as_deref(), Some("work"));
+        assert!(opts.command.is_none());
+
+        // Anything after the command, or after --, belongs to it
+        let opts = options("-- --help");
+        assert!(matches!(opts.command, Some(Command::Help)));
+        let opts = options("export -f");
+        assert!(matches!(
+            opts.command,
+            Some(Command::Export { file: Some(_), .. })
+        ));
+        assert!(opts.file.is_none());
+
+        assert!(matches!(options("--help").command, Some(Command::Help)));
+        assert!(matches!(options("-h").command, Some(Command::Help)));
+        assert_eq!(options("--restore-backup").restore_backup, Some(None));
+        assert_eq!(
+            options("--restore-backup 2 profiles").restore_backup,
+            Some(Some(2))
+        );
+
+        let error = |line: &str| Options::parse(&args(line)).err().unwrap().to_string();
+        assert_eq!(
+            error("--frobnicate"),
+            "unknown option '--frobnicate', try 'help'"
+        );
+        assert_eq!(error("-f"), "option '-f' requires a value");
+        assert_eq!(error("--profile"), "option '--profile' requires a value");
+    }
+
+    #[test]
+    fn counts_cannot_overflow() {
+        let mut store = test_store();
+        assert_eq!(
+            run(&mut store, "incr h a s o 2147483647").unwrap(),
+            Some(format!("{}/2", i32::MAX))
+        );
+        assert_eq!(
+            run(&mut store, "incr-target h a s o 2147483647").unwrap(),
+            Some(format!("{0}/{0}", i32::MAX))
+        );
+        assert_eq!(
+            run(&mut store, "decr h a s o -2147483648")
+                .unwrap_err()
+                .to_string(),
+            "expected a non-negative number, got '-2147483648'"
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use crate::application::{InputOp, InputSubject};
+use crate::cli::Change;
+
+// Commands and their arguments may be abbreviated, <what> is one of map, zone, ability or usage
+pub const SYNTAX: &[(&str, &str)] = &[
+    (
+        "new <what> [<parent>] <name>",
+        "Add a map, zone, ability or usage",
+    ),
+    (
+        "select <what> [<parent>] <name>",
+        "Select by name, abbreviation or index",
+    ),
+    (
+        "remove <what> [<parent>] <name>",
+        "Remove a map, zone, ability or usage",
+    ),
+    (
+        "rename <what> [<parent>] [<old>] <new>",
+        "Rename, the selection by default",
+    ),
+    ("incr [n], decr [n]", "Change the selected progress"),
+    ("progress [+|-]<n>", "Set or change the selected progress"),
+    ("target [+|-]<n>", "Set or change the selected target"),
+    (
+        "sort rows|columns [-]<order>",
+        "Sort by name, completion or recent",
+    ),
+    ("undo, redo", "Undo or redo the last change"),
+    ("list [<what>]", "List names with their indices"),
+    ("state", "Show the selection"),
+    ("save", "Save now"),
+    ("quit[!]", "Save and quit, or quit without saving"),
+    ("help", "Show this help"),
+];
+
+pub enum Line {
+    Input(InputOp, InputSubject, Vec<String>),
+    Progress(Change),
+    Target(Change),
+    List(InputSubject),
+    Undo,
+    Redo,
+    State,
+    Save,
+    Quit { save: bool },
+    Help,
+}
+
+#[derive(Clone, Copy)]
+enum Verb {
+    New,
+    Select,
+    Remove,
+    Rename,
+    Incr,
+    Decr,
+    Progress,
+    Target,
+    Sort,
+    Undo,
+    Redo,
+    List,
+    State,
+    Save,
+    Quit,
+    Help,
+}
+
+// Words may be abbreviated to any prefix, the first match in each list wins
+const VERBS: &[(&str, Verb)] = &[
+    ("new", Verb::New),
+    ("select", Verb::Select),
+    ("remove", Verb::Remove),
+    ("rename", Verb::Rename),
+    ("incr", Verb::Incr),
+    ("decr", Verb::Decr),
+    ("progress", Verb::Progress),
+    ("target", Verb::Target),
+    ("sort", Verb::Sort),
+    ("undo", Verb::Undo),
+    ("redo", Verb::Redo),
+    ("list", Verb::List),
+    ("state", Verb::State),
+    ("save", Verb::Save),
+    ("quit", Verb::Quit),
+    ("exit", Verb::Quit),
+    ("help", Verb::Help),
+];
+
+const SUBJECTS: &[(&str, InputSubject)] = &[
+    ("map", InputSubject::Map),
+    ("zone", InputSubject::Zone),
+    ("ability", InputSubject::Ability),
+    ("usage", InputSubject::Usage),
+    ("maps", InputSubject::Map),
+    ("zones", InputSubject::Zone),
+    ("abilities", InputSubject::Ability),
+    ("usages", InputSubject::Usage),
+];
+
+const AXES: &[(&str, InputSubject)] = &[
+    ("rows", InputSubject::Map),
+    ("columns", InputSubject::Ability),
+];
+
+pub fn parse(line: &str) -> Result<Option<Line>, String> {
+    let words = split(line)?;
+    let Some((verb, args)) = words.split_first() else {
+        return Ok(None);
+    };
+
+    let (verb, bang) = match verb.strip_suffix('!') {
+        Some(verb) => (verb, true),
+        None => (verb.as_str(), false),
+    };
+    let verb = expand(verb, VERBS).ok_or_else(|| format!("unknown command '{}'", verb))?;
+    if bang && !matches!(verb, Verb::Quit) {
+        return Err("only quit may be forced with !".to_string());
+    }
+
+    let line = match verb {
+        Verb::New | Verb::Select | Verb::Remove | Verb::Rename => {
+            let op = match verb {
+                Verb::New => InputOp::New,
+                Verb::Select => InputOp::Select,
+                Verb::Remove => InputOp::Remove,
+                _ => InputOp::Rename,
+            };
+            let (subject, args) = match args.split_first() {
+                Some((subject, args)) if !args.is_empty() => (subject, args),
+                _ => return Err("expected what to change and a name".to_string()),
+            };
+            Line::Input(op, word(subject, SUBJECTS)?, args.to_vec())
+        }
+        Verb::Incr | Verb::Decr => {
+            let n = match args {
+                [] => 1,
+                [n] => amount(n)?,
+                _ => return Err("expected at most one count".to_string()),
+            };
+            match verb {
+                Verb::Incr => Line::Progress(Change::By(n)),
+                _ => Line::Progress(Change::By(-n)),
+            }
+        }
+        Verb::Progress | Verb::Target => {
+            let [change] = args else {
+                return Err("expected a number, optionally prefixed by + or -".to_string());
+            };
+            let change = match change.chars().next() {
+                Some('+' | '-') => Change::By(count(change)?),
+                _ => Change::To(count(change)?),
+            };
+            match verb {
+                Verb::Progress => Line::Progress(change),
+                _ => Line::Target(change),
+            }
+        }
+        Verb::Sort => {
+            let [axis, order] = args else {
+                return Err("expected rows or columns and an order".to_string());
+            };
+            Line::Input(InputOp::Sort, word(axis, AXES)?, vec![order.clone()])
+        }
+        Verb::List => match args {
+            [] => Line::List(InputSubject::Map),
+            [subject] => Line::List(word(subject, SUBJECTS)?),
+            _ => return Err("expected at most one thing to list".to_string()),
+        },
+        Verb::Undo | Verb::Redo | Verb::State | Verb::Save | Verb::Quit | Verb::Help => {
+            if !args.is_empty() {
+                return Err("expected no arguments".to_string());
+            }
+            match verb {
+                Verb::Undo => Line::Undo,
+                Verb::Redo => Line::Redo,
+                Verb::State => Line::State,
+                Verb::Save => Line::Save,
+                Verb::Quit => Line::Quit { save: !bang },
+                _ => Line::Help,
+            }
+        }
+    };
+    Ok(Some(line))
+}
+
+fn expand<T: Copy>(word: &str, vocabulary: &[(&str, T)]) -> Option<T> {
+    if word.is_empty() {
+        return None;
+    }
+    let word = word.to_lowercase();
+    vocabulary
+        .iter()
+        .find(|(w, _)| w.starts_with(&word))
+        .map(|(_, t)| *t)
+}
+
+fn word<T: Copy>(word: &str, vocabulary: &[(&str, T)]) -> Result<T, String> {
+    expand(word, vocabulary).ok_or_else(|| {
+        let expected: Vec<_> = vocabulary.iter().map(|(w, _)| *w).collect();
+        format!(
+            "unknown '{}', expected one of {}",
+            word,
+            expected.join(", ")
+        )
+    })
+}
+
+fn count(n: &str) -> Result<i32, String> {
+    n.parse()
+        .map_err(|_| format!("expected a number, got '{}'", n))
+}
+
+// Negative amounts would turn incr into decr, and cannot all be negated
+fn amount(n: &str) -> Result<i32, String> {
+    n.parse()
+        .ok()
+        .filter(|n| *n >= 0)
+        .ok_or_else(|| format!("expected a non-negative number, got '{}'", n))
+}
+
+// Splits on whitespace, except within double quotes
+fn split(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quote".to_string());
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(line: &str) -> Line {
+        parse(line).unwrap().unwrap()
+    }
+
+    #[test]
+    fn words_may_be_abbreviated_and_quoted() {
+        assert!(matches!(
+            parsed("n z ascent a-main"),
+            Line::Input(InputOp::New, InputSubject::Zone, args) if args == ["ascent", "a-main"]
+        ));
+        assert!(matches!(
+            parsed(r#"SEL Abil "Sky Smoke""#),
+            Line::Input(InputOp::Select, InputSubject::Ability, args) if args == ["Sky Smoke"]
+        ));
+        assert!(matches!(
+            parsed("ren u Flash"),
+            Line::Input(InputOp::Rename, InputSubject::Usage, _)
+        ));
+        assert!(matches!(expand("re", VERBS), Some(Verb::Remove)));
+        assert!(matches!(expand("p", VERBS), Some(Verb::Progress)));
+        assert!(expand("", VERBS).is_none());
+
+        assert_eq!(split(r#"a "b c"  ""d "" "#).unwrap(), ["a", "b c", "d", ""]);
+        assert_eq!(split("  ").unwrap(), Vec::<String>::new());
+        assert!(parse("").unwrap().is_none());
+    }
+
+    #[test]
+    fn quitting_may_be_forced() {
+        assert!(matches!(parsed("q"), Line::Quit { save: true }));
+        assert!(matches!(parsed("quit!"), Line::Quit { save: false }));
+        assert!(matches!(parsed("exit!"), Line::Quit { save: false }));
+    }
+
+    #[test]
+    fn progress_is_set_or_changed() {
+        assert!(matches!(
+            parsed("progress +2"),
+            Line::Progress(Change::By(2))
+        ));
+        assert!(matches!(
+            parsed("progress -2"),
+            Line::Progress(Change::By(-2))
+        ));
+        assert!(matches!(
+            parsed("progress 5"),
+            Line::Progress(Change::To(5))
+        ));
+        assert!(matches!(parsed("t -1"), Line::Target(Change::By(-1))));
+        assert!(matches!(parsed("incr"), Line::Progress(Change::By(1))));
+        assert!(matches!(parsed("d 3"), Line::Progress(Change::By(-3))));
+    }
+
+    #[test]
+    fn mistakes_are_explained() {
+        let error = |line| parse(line).err().unwrap();
+        assert_eq!(error("frobnicate"), "unknown command 'frobnicate'");
+        assert_eq!(error("undo!"), "only quit may be forced with !");
+        assert_eq!(error(r#""" map Haven"#), "unknown command ''");
+        assert_eq!(error("new map"), "expected what to change and a name");
+        assert_eq!(
+            error("new thing Haven"),
+            "unknown 'thing', expected one of map, zone, ability, usage, maps, zones, abilities, \
+             usages"
+        );
+        assert_eq!(error("progress x"), "expected a number, got 'x'");
+        assert_eq!(
+            error("target"),
+            "expected a number, optionally prefixed by + or -"
+        );
+        assert_eq!(error("incr 1 2"), "expected at most one count");
+        assert_eq!(
+            error("decr -2147483648"),
+            "expected a non-negative number, got '-2147483648'"
+        );
+        assert_eq!(error("sort rows"), "expected rows or columns and an order");
+        assert_eq!(error("save now"), "expected no arguments");
+        assert_eq!(error(r#"select map "A Main"#), "unterminated quote");
+    }
+}
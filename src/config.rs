@@ -0,0 +1,111 @@
+use crate::keys::{Action, Key, Keymap};
+use serde::Deserialize as Deserialise;
+use shellexpand::tilde;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const CONFIG_VAR: &str = "LINEUP_TRACKER_CONFIG";
+const CONFIG_DIR_NAME: &str = "lineup-tracker";
+const CONFIG_NAME: &str = "config.json";
+
+pub struct Config {
+    pub keys: Keymap,
+    pub confirm_removals: bool,
+    pub case_insensitive_names: bool,
+    pub autosave: Option<Duration>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            keys: Keymap::default(),
+            confirm_removals: true,
+            case_insensitive_names: false,
+            autosave: None,
+        }
+    }
+}
+
+#[derive(Default, Deserialise)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct RawConfig {
+    keys: HashMap<Action, Vec<String>>,
+    confirm_removals: Option<bool>,
+    case_insensitive_names: bool,
+    autosave_interval: Option<u64>,
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        Self::parse(&data).map_err(|msg| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("bad config file {}: {}", path.display(), msg),
+            )
+        })
+    }
+
+    fn parse(data: &[u8]) -> std::result::Result<Self, String> {
+        let raw: RawConfig = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+
+        let mut overrides = HashMap::new();
+        for (action, keys) in raw.keys {
+            let keys = keys
+                .iter()
+                .map(|k| k.parse())
+                .collect::<std::result::Result<Vec<Key>, _>>()?;
+            overrides.insert(action, keys);
+        }
+        Ok(Self {
+            keys: Keymap::with_overrides(&overrides)?,
+            confirm_removals: raw.confirm_removals.unwrap_or(true),
+            case_insensitive_names: raw.case_insensitive_names,
+            // Autosaves write over the save file, so quitting without saving keeps what they saved
+            autosave: raw
+                .autosave_interval
+                .filter(|secs| *secs != 0)
+                .map(Duration::from_secs),
+        })
+    }
+
+    fn path() -> PathBuf {
+        if let Ok(path) = env::var(CONFIG_VAR) {
+            if !path.is_empty() {
+                return PathBuf::from(tilde(&path).as_ref());
+            }
+        }
+        let base = match env::var("XDG_CONFIG_HOME") {
+            Ok(dir) if Path::new(&dir).is_absolute() => PathBuf::from(dir),
+            _ => PathBuf::from(tilde("~/.config").as_ref()),
+        };
+        base.join(CONFIG_DIR_NAME).join(CONFIG_NAME)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn autosaves_are_opt_in() {
+        assert_eq!(Config::default().autosave, None);
+        assert_eq!(Config::parse(b"{}").unwrap().autosave, None);
+        let parsed = |data: &str| Config::parse(data.as_bytes()).map(|c| c.autosave);
+        assert_eq!(parsed(r#"{"autosave-interval": 0}"#), Ok(None));
+        assert_eq!(
+            parsed(r#"{"autosave-interval": 30}"#),
+            Ok(Some(Duration::from_secs(30)))
+        );
+        assert!(parsed(r#"{"autosave-interval": -1}"#).is_err());
+    }
+}
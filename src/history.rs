@@ -0,0 +1,230 @@
+use crate::model::{Arrangement, Id, Item, LineupKey, ModelError, ProgressStore, Target};
+
+#[derive(Clone, Debug)]
+pub enum Edit {
+    Target {
+        key: LineupKey,
+        from: Target,
+        to: Target,
+    },
+    Insert {
+        item: Item,
+        targets: Vec<(LineupKey, Target)>,
+    },
+    Delete {
+        item: Item,
+        targets: Vec<(LineupKey, Target)>,
+    },
+    Rename {
+        id: Id,
+        from: String,
+        to: String,
+    },
+    Arrange {
+        from: Arrangement,
+        to: Arrangement,
+    },
+}
+
+impl Edit {
+    // Describes the addition of something which has just been added
+    pub fn insertion(store: &ProgressStore, id: Id) -> Option<Self> {
+        Some(Edit::Insert {
+            item: store.item(id)?,
+            targets: store.targets_of(id),
+        })
+    }
+
+    // Describes the removal of something which is still present
+    pub fn deletion(store: &ProgressStore, id: Id) -> Option<Self> {
+        Some(Edit::Delete {
+            item: store.item(id)?,
+            targets: store.targets_of(id),
+        })
+    }
+
+    pub fn apply(&self, store: &mut ProgressStore) -> Result<(), ModelError> {
+        match self {
+            Edit::Target { key, to, .. } => return store.set_target_of(*key, to.clone()),
+            Edit::Insert { item, targets } => return store.insert_item(item, targets),
+            Edit::Delete { item, .. } => return store.delete_item(item.id()),
+            Edit::Rename { id, to, .. } => return store.rename_item(*id, to),
+            Edit::Arrange { to, .. } => store.arrange(to),
+        }
+        Ok(())
+    }
+
+    pub fn inverse(&self) -> Self {
+        match self.clone() {
+            Edit::Target { key, from, to } => Edit::Target {
+                key,
+                from: to,
+                to: from,
+            },
+            Edit::Insert { item, targets } => Edit::Delete { item, targets },
+            Edit::Delete { item, targets } => Edit::Insert { item, targets },
+            Edit::Rename { id, from, to } => Edit::Rename {
+                id,
+                from: to,
+                to: from,
+            },
+            Edit::Arrange { from, to } => Edit::Arrange { from: to, to: from },
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Edit::Target { from, to, .. } => format!(
+                "changing {}/{} to {}/{}",
+                from.progress, from.target, to.progress, to.target
+            ),
+            Edit::Insert { item, .. } => format!("adding {}", item.describe()),
+            Edit::Delete { item, .. } => format!("removing {}", item.describe()),
+            Edit::Rename { from, to, .. } => format!("renaming '{}' to '{}'", from, to),
+            Edit::Arrange { .. } => "reordering".to_string(),
+        }
+    }
+}
+
+// Edits made this session, most recent last
+#[derive(Default)]
+pub struct History {
+    done: Vec<Edit>,
+    undone: Vec<Edit>,
+}
+
+impl History {
+    pub fn record(&mut self, edit: Edit) {
+        self.done.push(edit);
+        self.undone.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+
+    // Edits which cannot be undone or redone stay where they were
+    pub fn undo(&mut self, store: &mut ProgressStore) -> Result<Option<&Edit>, ModelError> {
+        let Some(edit) = self.done.pop() else {
+            return Ok(None);
+        };
+        if let Err(e) = edit.inverse().apply(store) {
+            self.done.push(edit);
+            return Err(e);
+        }
+        self.undone.push(edit);
+        Ok(self.undone.last())
+    }
+
+    pub fn redo(&mut self, store: &mut ProgressStore) -> Result<Option<&Edit>, ModelError> {
+        let Some(edit) = self.undone.pop() else {
+            return Ok(None);
+        };
+        if let Err(e) = edit.apply(store) {
+            self.undone.push(edit);
+            return Err(e);
+        }
+        self.done.push(edit);
+        Ok(self.done.last())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{test_store, SortKey};
+    use crate::selection::Selector;
+
+    fn snapshot(store: &ProgressStore) -> String {
+        serde_json::to_string(store).unwrap()
+    }
+
+    // Applies and records the edit, then checks that undoing and redoing it round trip
+    fn round_trip(store: &mut ProgressStore, edit: Edit) {
+        let before = snapshot(store);
+        edit.apply(store).unwrap();
+        let after = snapshot(store);
+        assert_ne!(before, after);
+
+        let mut history = History::default();
+        history.record(edit);
+        assert!(history.undo(store).unwrap().is_some());
+        assert_eq!(snapshot(store), before);
+        assert!(history.undo(store).unwrap().is_none());
+        assert!(history.redo(store).unwrap().is_some());
+        assert_eq!(snapshot(store), after);
+        assert!(history.redo(store).unwrap().is_none());
+    }
+
+    #[test]
+    fn edits_round_trip() {
+        let mut store = test_store();
+        let key = *store.progress.keys().next().unwrap();
+        let from = store.progress[&key].clone();
+        let mut to = from.clone();
+        to.set_target(5);
+        let edit = Edit::Target { key, from, to };
+        assert_eq!(edit.inverse().describe(), "changing 0/5 to 0/2");
+        round_trip(&mut store, edit);
+
+        // Deleting the first zone must put it and its progress back in front of the second
+        let a_main = store.maps[0].zones[0].id;
+        let edit = Edit::deletion(&store, a_main).unwrap();
+        assert_eq!(store.targets_of(a_main).len(), 2);
+        round_trip(&mut store, edit.clone());
+        assert!(store.targets_of(a_main).is_empty());
+        round_trip(&mut store, edit.inverse());
+        assert_eq!(store.maps[0].zones[0].name, "A Main");
+        assert_eq!(store.targets_of(a_main).len(), 2);
+
+        let smoke = store.abilities[0].id;
+        let edit = Edit::Rename {
+            id: smoke,
+            from: "Smoke".into(),
+            to: "Molly".into(),
+        };
+        round_trip(&mut store, edit);
+
+        let from = store.arrangement();
+        let mut sorted = test_store();
+        sorted.sort_rows(SortKey::Name, true);
+        let edit = Edit::Arrange {
+            from,
+            to: sorted.arrangement(),
+        };
+        round_trip(&mut store, edit);
+    }
+
+    #[test]
+    fn failed_edits_stay_put() {
+        let mut store = test_store();
+        let garage = store.maps[0].zones[1].id;
+        let edit = Edit::Rename {
+            id: garage,
+            from: "Garage".into(),
+            to: "Hookah".into(),
+        };
+        edit.apply(&mut store).unwrap();
+        let mut history = History::default();
+        history.record(edit);
+
+        // Taking the old name elsewhere stops the rename being undone
+        store
+            .add_zone(&Selector::Index(0), "Garage".into())
+            .unwrap();
+        let before = snapshot(&store);
+        for _ in 0..2 {
+            assert!(matches!(
+                history.undo(&mut store),
+                Err(ModelError::Duplicate("zone", _))
+            ));
+        }
+        assert_eq!(snapshot(&store), before);
+        assert!(history.redo(&mut store).unwrap().is_none());
+
+        store.rm_zone("Haven", "Garage").unwrap();
+        assert!(history.undo(&mut store).unwrap().is_some());
+        assert_eq!(store.maps[0].zones[1].name, "Garage");
+    }
+}
@@ -0,0 +1,105 @@
+use crate::model::{LineupKey, ProgressStore, Target};
+use serde::{Deserialize as Deserialise, Serialize as Serialise};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Result, Write};
+use std::path::PathBuf;
+
+// Changes to targets made since the store was last saved, one per line. Saving or quitting removes
+// the journal, so finding one on startup means that the last session did not end cleanly.
+pub struct Journal {
+    path: PathBuf,
+}
+
+#[derive(Serialise, Deserialise)]
+struct Entry {
+    key: LineupKey,
+    target: Target,
+}
+
+impl Journal {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn append(&self, key: LineupKey, target: &Target) -> Result<()> {
+        let mut line = serde_json::to_vec(&Entry {
+            key,
+            target: target.clone(),
+        })?;
+        line.push(b'\n');
+
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        f.write_all(&line)?;
+        f.sync_data()
+    }
+
+    // Returns how many entries were replayed and how many were not, or None if there is no journal
+    pub fn replay(&self, store: &mut ProgressStore) -> Result<Option<(usize, usize)>> {
+        let data = match fs::read_to_string(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut replayed = 0;
+        let mut skipped = 0;
+        for line in data.lines().filter(|l| !l.trim().is_empty()) {
+            // The last line is torn if the tracker died while writing it
+            match serde_json::from_str::<Entry>(line)
+                .ok()
+                .map(|entry| store.set_target_of(entry.key, entry.target))
+            {
+                Some(Ok(())) => replayed += 1,
+                _ => skipped += 1,
+            }
+        }
+        Ok(Some((replayed, skipped)))
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::test_store;
+    use crate::selection::Selection;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn replays_known_lineups() {
+        let mut store = test_store();
+        let key = store.key(&Selection::default()).unwrap();
+
+        let path = env::temp_dir().join(format!("lineup-journal-{}", process::id()));
+        let journal = Journal::new(path.clone());
+        journal.clear().unwrap();
+        assert_eq!(journal.replay(&mut store).unwrap(), None);
+
+        let target = Target {
+            progress: 2,
+            target: 3,
+            last_practised: None,
+        };
+        journal.append(key, &target).unwrap();
+        let missing = (key.0, key.1, key.2, key.0);
+        journal.append(missing, &target).unwrap();
+        fs::write(&path, fs::read_to_string(&path).unwrap() + "{\"key\": [").unwrap();
+
+        assert_eq!(journal.replay(&mut store).unwrap(), Some((1, 2)));
+        assert_eq!(store.progress.get(&key), Some(&target));
+        assert!(!store.progress.contains_key(&missing));
+
+        journal.clear().unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,460 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize as Deserialise, Serialize as Serialise};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialise, Deserialise)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Help,
+    CommandLine,
+    Undo,
+    Redo,
+    AbbreviateUsages,
+    SaveAndQuit,
+    Quit,
+    Interrupt,
+    PrevZone,
+    NextZone,
+    PrevUsage,
+    NextUsage,
+    IncrProgress,
+    DecrProgress,
+    IncrTarget,
+    DecrTarget,
+    MatchProgressToTarget,
+    MatchTargetToProgress,
+    ZeroTarget,
+    ZeroProgress,
+    NewMap,
+    NewZone,
+    NewAbility,
+    NewUsage,
+    SelectMap,
+    SelectZone,
+    SelectAbility,
+    SelectUsage,
+    RemoveMap,
+    RemoveZone,
+    RemoveAbility,
+    RemoveUsage,
+    RenameMap,
+    RenameZone,
+    RenameAbility,
+    RenameUsage,
+    MoveMapUp,
+    MoveMapDown,
+    MoveZoneUp,
+    MoveZoneDown,
+    MoveAbilityLeft,
+    MoveAbilityRight,
+    MoveUsageLeft,
+    MoveUsageRight,
+    SortRows,
+    SortColumns,
+}
+
+// Actions are displayed by the names used for them in the config file
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match serde_json::to_value(self) {
+            Ok(Value::String(name)) => f.write_str(&name),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Category {
+    General,
+    Navigation,
+    Progress,
+    Editing,
+    Arranging,
+}
+
+pub const CATEGORIES: [Category; 5] = [
+    Category::General,
+    Category::Navigation,
+    Category::Progress,
+    Category::Editing,
+    Category::Arranging,
+];
+
+impl Category {
+    pub fn title(self) -> &'static str {
+        match self {
+            Category::General => "General",
+            Category::Navigation => "Navigation",
+            Category::Progress => "Progress",
+            Category::Editing => "Editing",
+            Category::Arranging => "Arranging",
+        }
+    }
+}
+
+impl Action {
+    pub fn category(self) -> Category {
+        use Action::*;
+        match self {
+            Help | CommandLine | Undo | Redo | AbbreviateUsages | SaveAndQuit | Quit
+            | Interrupt => Category::General,
+            PrevZone | NextZone | PrevUsage | NextUsage => Category::Navigation,
+            IncrProgress
+            | DecrProgress
+            | IncrTarget
+            | DecrTarget
+            | MatchProgressToTarget
+            | MatchTargetToProgress
+            | ZeroTarget
+            | ZeroProgress => Category::Progress,
+            NewMap | NewZone | NewAbility | NewUsage | SelectMap | SelectZone | SelectAbility
+            | SelectUsage | RemoveMap | RemoveZone | RemoveAbility | RemoveUsage | RenameMap
+            | RenameZone | RenameAbility | RenameUsage => Category::Editing,
+            MoveMapUp | MoveMapDown | MoveZoneUp | MoveZoneDown | MoveAbilityLeft
+            | MoveAbilityRight | MoveUsageLeft | MoveUsageRight | SortRows | SortColumns => {
+                Category::Arranging
+            }
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        use Action::*;
+        match self {
+            Help => "Show this help",
+            CommandLine => "Enter a command",
+            Undo => "Undo the last change",
+            Redo => "Redo the last undone change",
+            AbbreviateUsages => "Abbreviate usage names to fit",
+            SaveAndQuit => "Save and quit",
+            Quit => "Quit without saving",
+            Interrupt => "Abort without saving",
+            PrevZone => "Select the previous zone",
+            NextZone => "Select the next zone",
+            PrevUsage => "Select the previous usage",
+            NextUsage => "Select the next usage",
+            IncrProgress => "Increase progress",
+            DecrProgress => "Decrease progress",
+            IncrTarget => "Increase target",
+            DecrTarget => "Decrease target",
+            MatchProgressToTarget => "Set progress to the target",
+            MatchTargetToProgress => "Set target to the progress",
+            ZeroTarget => "Set target to zero",
+            ZeroProgress => "Set progress to zero",
+            NewMap => "Add a map",
+            NewZone => "Add a zone to the selected map",
+            NewAbility => "Add an ability",
+            NewUsage => "Add a usage to the selected ability",
+            SelectMap => "Select a map by name",
+            SelectZone => "Select a zone by name",
+            SelectAbility => "Select an ability by name",
+            SelectUsage => "Select a usage by name",
+            RemoveMap => "Remove a map",
+            RemoveZone => "Remove a zone",
+            RemoveAbility => "Remove an ability",
+            RemoveUsage => "Remove a usage",
+            RenameMap => "Rename the selected map",
+            RenameZone => "Rename the selected zone",
+            RenameAbility => "Rename the selected ability",
+            RenameUsage => "Rename the selected usage",
+            MoveMapUp => "Move the selected map up",
+            MoveMapDown => "Move the selected map down",
+            MoveZoneUp => "Move the selected zone up",
+            MoveZoneDown => "Move the selected zone down",
+            MoveAbilityLeft => "Move the selected ability left",
+            MoveAbilityRight => "Move the selected ability right",
+            MoveUsageLeft => "Move the selected usage left",
+            MoveUsageRight => "Move the selected usage right",
+            SortRows => "Sort maps and zones",
+            SortColumns => "Sort abilities and usages",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    const fn plain(code: KeyCode) -> Self {
+        Self {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    const fn ctrl(code: KeyCode) -> Self {
+        Self {
+            code,
+            modifiers: KeyModifiers::CONTROL,
+        }
+    }
+
+    const fn char(c: char) -> Self {
+        Self::plain(KeyCode::Char(c))
+    }
+}
+
+// Shift is already reflected in the case of characters and is ignored elsewhere
+impl From<&KeyEvent> for Key {
+    fn from(ev: &KeyEvent) -> Self {
+        Self {
+            code: ev.code,
+            modifiers: ev.modifiers - KeyModifiers::SHIFT,
+        }
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::Up => write!(f, "Up"),
+            KeyCode::Down => write!(f, "Down"),
+            KeyCode::Left => write!(f, "Left"),
+            KeyCode::Right => write!(f, "Right"),
+            KeyCode::Home => write!(f, "Home"),
+            KeyCode::End => write!(f, "End"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Delete => write!(f, "Delete"),
+            KeyCode::Insert => write!(f, "Insert"),
+            KeyCode::Esc => write!(f, "Esc"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+// Parses keys as they are displayed, such as "a", "Ctrl-Up" or "F5"
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            let lower = rest.to_lowercase();
+            if rest.chars().count() > 1 && lower.starts_with("ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = &rest["ctrl-".len()..];
+            } else if rest.chars().count() > 1 && lower.starts_with("alt-") {
+                modifiers |= KeyModifiers::ALT;
+                rest = &rest["alt-".len()..];
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "enter" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "esc" => KeyCode::Esc,
+                name => match name.strip_prefix('f').map(str::parse) {
+                    Some(Ok(n)) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key '{}'", s)),
+                },
+            },
+        };
+        Ok(Self { code, modifiers })
+    }
+}
+
+// Earlier bindings take precedence and are listed first in the help
+const DEFAULT_BINDINGS: &[(Key, Action)] = &[
+    (Key::char('?'), Action::Help),
+    (Key::char(':'), Action::CommandLine),
+    (Key::ctrl(KeyCode::Char('z')), Action::Undo),
+    (Key::ctrl(KeyCode::Char('r')), Action::Redo),
+    (Key::ctrl(KeyCode::Char('y')), Action::Redo),
+    (Key::char('b'), Action::AbbreviateUsages),
+    (Key::char('Q'), Action::SaveAndQuit),
+    (Key::char('!'), Action::Quit),
+    (Key::ctrl(KeyCode::Char('c')), Action::Interrupt),
+    (Key::char('k'), Action::PrevZone),
+    (Key::plain(KeyCode::Up), Action::PrevZone),
+    (Key::char('j'), Action::NextZone),
+    (Key::plain(KeyCode::Down), Action::NextZone),
+    (Key::char('h'), Action::PrevUsage),
+    (Key::plain(KeyCode::Left), Action::PrevUsage),
+    (Key::char('l'), Action::NextUsage),
+    (Key::plain(KeyCode::Right), Action::NextUsage),
+    (Key::char('y'), Action::IncrProgress),
+    (Key::char('Y'), Action::DecrProgress),
+    (Key::char('u'), Action::IncrTarget),
+    (Key::char('U'), Action::DecrTarget),
+    (Key::char('i'), Action::MatchProgressToTarget),
+    (Key::char('I'), Action::MatchTargetToProgress),
+    (Key::char('o'), Action::ZeroTarget),
+    (Key::char('O'), Action::ZeroProgress),
+    (Key::char('q'), Action::NewMap),
+    (Key::char('w'), Action::NewZone),
+    (Key::char('e'), Action::NewAbility),
+    (Key::char('r'), Action::NewUsage),
+    (Key::char('a'), Action::SelectMap),
+    (Key::char('s'), Action::SelectZone),
+    (Key::char('d'), Action::SelectAbility),
+    (Key::char('f'), Action::SelectUsage),
+    (Key::char('z'), Action::RemoveMap),
+    (Key::char('x'), Action::RemoveZone),
+    (Key::char('c'), Action::RemoveAbility),
+    (Key::char('v'), Action::RemoveUsage),
+    (Key::char('1'), Action::RenameMap),
+    (Key::char('2'), Action::RenameZone),
+    (Key::char('3'), Action::RenameAbility),
+    (Key::char('4'), Action::RenameUsage),
+    (Key::ctrl(KeyCode::Up), Action::MoveMapUp),
+    (Key::ctrl(KeyCode::Down), Action::MoveMapDown),
+    (Key::char('K'), Action::MoveZoneUp),
+    (Key::char('J'), Action::MoveZoneDown),
+    (Key::ctrl(KeyCode::Left), Action::MoveAbilityLeft),
+    (Key::ctrl(KeyCode::Right), Action::MoveAbilityRight),
+    (Key::char('H'), Action::MoveUsageLeft),
+    (Key::char('L'), Action::MoveUsageRight),
+    (Key::char('g'), Action::SortRows),
+    (Key::char('G'), Action::SortColumns),
+];
+
+pub struct Keymap {
+    bindings: Vec<(Key, Action)>,
+}
+
+impl Keymap {
+    // Actions given keys replace all of their default bindings, an empty list unbinds them
+    pub fn with_overrides(overrides: &HashMap<Action, Vec<Key>>) -> Result<Self, String> {
+        let mut bindings = Vec::new();
+        for (key, action) in DEFAULT_BINDINGS {
+            match overrides.get(action) {
+                None => bindings.push((*key, *action)),
+                Some(keys) => {
+                    if !bindings.iter().any(|(_, a)| a == action) {
+                        bindings.extend(keys.iter().map(|k| (*k, *action)));
+                    }
+                }
+            }
+        }
+
+        let mut bound: HashMap<Key, Action> = HashMap::new();
+        for (key, action) in &bindings {
+            match bound.insert(*key, *action) {
+                Some(other) if other != *action => {
+                    return Err(format!(
+                        "'{}' is bound to both {} and {}",
+                        key, other, action
+                    ))
+                }
+                _ => {}
+            }
+        }
+        Ok(Self { bindings })
+    }
+
+    pub fn action(&self, ev: &KeyEvent) -> Option<Action> {
+        let key = Key::from(ev);
+        self.bindings
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, action)| *action)
+    }
+
+    pub fn keys_for(&self, action: Action) -> Vec<Key> {
+        self.bindings
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(k, _)| *k)
+            .collect()
+    }
+
+    // Every bound action in binding order, once each
+    pub fn actions(&self) -> Vec<Action> {
+        let mut actions: Vec<Action> = Vec::new();
+        for (_, action) in &self.bindings {
+            if !actions.contains(action) {
+                actions.push(*action);
+            }
+        }
+        actions
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: DEFAULT_BINDINGS.to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_parsed_by_name() {
+        let key = |s: &str| s.parse::<Key>();
+        assert_eq!(key("Ctrl-a"), Ok(Key::ctrl(KeyCode::Char('a'))));
+        assert_eq!(key("F5"), Ok(Key::plain(KeyCode::F(5))));
+        assert_eq!(key("space"), Ok(Key::char(' ')));
+        assert_eq!(key("-"), Ok(Key::char('-')));
+        assert_eq!(
+            key("alt-ctrl-Up").map(|k| k.modifiers),
+            Ok(KeyModifiers::ALT | KeyModifiers::CONTROL)
+        );
+        for name in ["F13", "Hyper", "Ctrl-", ""] {
+            assert_eq!(key(name), Err(format!("unknown key '{}'", name)));
+        }
+    }
+
+    #[test]
+    fn rebindings_replace_defaults() {
+        let ctrl_u = Key::ctrl(KeyCode::Char('u'));
+        let keymap = Keymap::with_overrides(&HashMap::from([
+            (Action::Undo, vec![ctrl_u]),
+            (Action::Redo, vec![]),
+        ]))
+        .unwrap();
+        assert_eq!(keymap.keys_for(Action::Undo), [ctrl_u]);
+        assert!(keymap.keys_for(Action::Redo).is_empty());
+        assert_eq!(keymap.keys_for(Action::Help), [Key::char('?')]);
+
+        let clash = HashMap::from([(Action::Undo, vec![Key::char('Q')])]);
+        assert_eq!(
+            Keymap::with_overrides(&clash).err().unwrap(),
+            "'Q' is bound to both undo and save-and-quit"
+        );
+        let moved = HashMap::from([
+            (Action::Undo, vec![Key::char('Q')]),
+            (Action::SaveAndQuit, vec![Key::char('X')]),
+        ]);
+        assert!(Keymap::with_overrides(&moved).is_ok());
+    }
+}
@@ -1,52 +1,562 @@
 mod application;
+mod cli;
+mod command_line;
+mod config;
+mod history;
+mod journal;
+mod keys;
+mod migration;
 mod model;
 mod render;
 mod selection;
+mod spreadsheet;
+mod storage;
 
 use crate::application::{App, FinalAction};
+use crate::cli::{Command, Options, USAGE};
+use crate::config::Config;
+use crate::migration::LoadError;
+use crate::model::ProgressStore;
+use crate::storage::SaveLocation;
 use crossterm::{
+    cursor::Show,
     event::{DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::fs::File;
-use std::io::{stdout, Result};
-use std::io::{BufReader, BufWriter};
+use serde_json::error::Category;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::flag;
+use std::cell::RefCell;
+use std::env;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::io::{stderr, stdin, stdout, Error, ErrorKind, IsTerminal, Result, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use tui::{backend::CrosstermBackend, Terminal};
-use shellexpand::tilde;
 
-const SAVE_LOC: &str = "~/.lineup-progress-rs.json";
+#[cfg(unix)]
+const STOP_SIGNALS: &[i32] = &[SIGINT, SIGTERM, signal_hook::consts::SIGHUP];
+#[cfg(not(unix))]
+const STOP_SIGNALS: &[i32] = &[SIGINT, SIGTERM];
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(e) = run(&args) {
+        eprintln!("lineup-tracker: {}", e);
+        exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<()> {
+    let opts = Options::parse(args)?;
+    let mut save_loc = SaveLocation::resolve(opts.file, opts.profile)?;
+    if let Some(backups) = opts.backups {
+        save_loc.backups = backups;
+    }
+
+    if let Some(idx) = opts.restore_backup {
+        return match opts.command {
+            Some(_) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "--restore-backup cannot be used with a command",
+            )),
+            None => restore_backup(&save_loc, idx),
+        };
+    }
+
+    match opts.command {
+        Some(Command::Help) => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        Some(Command::Profiles) => list_profiles(),
+        Some(cmd) => run_command(cmd, &save_loc),
+        None => run_interactive(&save_loc),
+    }
+}
+
+fn load(save_loc: &SaveLocation) -> Result<App> {
+    if let Some(app) = offer_recovery(save_loc)? {
+        return Ok(app);
+    }
+
+    let mut app = read(save_loc)?;
+    replay(&mut app, save_loc)?;
+    Ok(app)
+}
+
+fn read(save_loc: &SaveLocation) -> Result<App> {
+    let data = match fs::read(&save_loc.path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(App::new(save_loc.store_name())),
+        Err(e) => return Err(e),
+    };
+    match App::load(data.as_slice()) {
+        Ok(app) => Ok(app),
+        Err(LoadError::Damaged(e)) => recover(save_loc, &data, e),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn recover(save_loc: &SaveLocation, data: &[u8], err: serde_json::Error) -> Result<App> {
+    let kind = match err.classify() {
+        Category::Syntax => "a syntax error",
+        Category::Data => "unexpected data",
+        Category::Eof => "an unexpected end of file",
+        Category::Io => "a read error",
+    };
+    eprintln!(
+        "Could not load {}: found {} at line {}, column {}",
+        save_loc.path.display(),
+        kind,
+        err.line(),
+        err.column()
+    );
+    eprintln!("  {}", err);
+
+    if !stdin().is_terminal() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "refusing to touch a damaged save file non-interactively",
+        ));
+    }
+
+    let salvage = App::salvage(data, &save_loc.store_name());
+    let question = match &salvage {
+        Some((_, losses)) => {
+            if !losses.is_empty() {
+                eprintln!("Part of it can be salvaged, losing:");
+                for loss in losses {
+                    eprintln!("  - {}", loss);
+                }
+            }
+            "[s]alvage what remains, start [f]resh or [q]uit?"
+        }
+        None => {
+            eprintln!("Nothing can be salvaged from it.");
+            "Start [f]resh or [q]uit?"
+        }
+    };
+
+    let app = match (prompt(question)?, salvage) {
+        (Some('s'), Some((app, _))) => app,
+        (Some('f'), _) => App::new(save_loc.store_name()),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "left the damaged save file untouched",
+            ))
+        }
+    };
+    let aside = save_loc.quarantine()?;
+    eprintln!("The damaged file was moved to {}", aside.display());
+    Ok(app)
+}
+
+fn replay(app: &mut App, save_loc: &SaveLocation) -> Result<()> {
+    let Some((replayed, skipped)) = save_loc.journal().replay(&mut app.progress)? else {
+        return Ok(());
+    };
+    eprintln!(
+        "The last session ended without saving; replayed {} change{} to progress",
+        replayed,
+        if replayed == 1 { "" } else { "s" }
+    );
+    if skipped > 0 {
+        eprintln!("  {} could not be replayed", skipped);
+    }
+    save(app, save_loc)
+}
+
+fn offer_recovery(save_loc: &SaveLocation) -> Result<Option<App>> {
+    let path = save_loc.recovery_path();
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if !stdin().is_terminal() {
+        eprintln!(
+            "Unsaved changes to {} were kept in {}",
+            save_loc.path.display(),
+            path.display()
+        );
+        return Ok(None);
+    }
+
+    eprintln!(
+        "Unsaved changes to {} were kept in {} when the tracker last stopped unexpectedly.",
+        save_loc.path.display(),
+        path.display()
+    );
+    let app = match App::load(data.as_slice()) {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("They cannot be restored: {}", e);
+            if prompt("[d]iscard them or [k]eep them for later?")? == Some('d') {
+                fs::remove_file(&path)?;
+            }
+            return Ok(None);
+        }
+    };
+    match prompt("[r]estore them, [d]iscard them or [k]eep them for later?")? {
+        Some('r') => {
+            save(&app, save_loc)?;
+            fs::remove_file(&path)?;
+            Ok(Some(app))
+        }
+        Some('d') => {
+            fs::remove_file(&path)?;
+            save_loc.journal().clear()?;
+            Ok(None)
+        }
+        _ => Ok(None),
+    }
+}
+
+// Keeps whatever is in memory next to the save file, unless it is already saved.
+fn rescue(app: &App, save_loc: &SaveLocation) {
+    let mut data = Vec::new();
+    if app.save(&mut data).is_err() {
+        eprintln!("lineup-tracker: could not keep unsaved changes");
+        return;
+    }
+    // Compared as saved by this version, as the file may have been written by an older one
+    let mut saved = Vec::new();
+    if peek(save_loc).is_ok_and(|old| old.save(&mut saved).is_ok() && saved == data) {
+        return;
+    }
+    match save_loc.write_recovery(&data) {
+        Ok(path) => eprintln!(
+            "lineup-tracker: unsaved changes were kept in {}",
+            path.display()
+        ),
+        Err(e) => eprintln!("lineup-tracker: could not keep unsaved changes: {}", e),
+    }
+}
+
+fn restore_terminal() -> Result<()> {
+    disable_raw_mode()?;
+    execute!(stdout(), LeaveAlternateScreen, DisableMouseCapture, Show)
+}
+
+// Loads without touching anything on disk
+fn peek(save_loc: &SaveLocation) -> Result<App> {
+    match fs::read(&save_loc.path) {
+        Ok(data) => Ok(App::load(data.as_slice())?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(App::new(save_loc.store_name())),
+        Err(e) => Err(e),
+    }
+}
+
+fn in_use(save_loc: &SaveLocation) -> Error {
+    Error::new(
+        ErrorKind::ResourceBusy,
+        format!("{} is open in another tracker", save_loc.path.display()),
+    )
+}
+
+// Saves unless something else has changed the file since this tracker last read or wrote it
+fn save_seen(app: &App, save_loc: &SaveLocation, seen: &mut Option<Vec<u8>>) -> Result<()> {
+    let _writing = save_loc.lock_for_writing()?;
+    if fs::read(&save_loc.path).ok() != *seen {
+        return Err(Error::other(
+            "the save file was just changed elsewhere; save again once it is reloaded",
+        ));
+    }
+    save(app, save_loc)?;
+    *seen = fs::read(&save_loc.path).ok();
+    Ok(())
+}
+
+// Merges in changes made to the save file elsewhere, returning how many lineups were changed in both
+fn reload(
+    progress: &mut ProgressStore,
+    save_loc: &SaveLocation,
+    seen: &mut Option<Vec<u8>>,
+) -> Result<Option<usize>> {
+    let data = match fs::read(&save_loc.path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if seen.as_ref() == Some(&data) {
+        return Ok(None);
+    }
+
+    let theirs = App::load(data.as_slice())?.progress;
+    let base = match seen {
+        Some(seen) => App::load(seen.as_slice())?.progress,
+        None => ProgressStore::new(save_loc.store_name()),
+    };
+    let conflicts = progress.merge(&base, &theirs).ok_or_else(|| {
+        Error::other("the maps or abilities were changed both here and elsewhere")
+    })?;
+    *seen = Some(data);
+    Ok(Some(conflicts))
+}
+
+// Offers to combine changes made elsewhere with those made here, returning whether to save
+fn merge(
+    app: &mut App,
+    save_loc: &SaveLocation,
+    seen: &Option<Vec<u8>>,
+    disk: &Option<Vec<u8>>,
+) -> Result<bool> {
+    eprintln!(
+        "{} was changed by something else while it was open.",
+        save_loc.path.display()
+    );
+    let base = match seen {
+        Some(data) => App::load(data.as_slice()).ok().map(|app| app.progress),
+        None => Some(ProgressStore::new(save_loc.store_name())),
+    };
+    let theirs = disk
+        .as_ref()
+        .and_then(|data| App::load(data.as_slice()).ok());
+    let mut question =
+        "[m]erge their changes with yours, [o]verwrite them or [q]uit without saving?";
+    if theirs.is_none() {
+        eprintln!("It can no longer be read.");
+        question = "[o]verwrite it or [q]uit without saving?";
+    }
+
+    loop {
+        match (prompt(question)?, &base, &theirs) {
+            (Some('m'), Some(base), Some(theirs)) => {
+                match app.progress.merge(base, &theirs.progress) {
+                    Some(0) => return Ok(true),
+                    Some(n) => {
+                        eprintln!(
+                            "{} lineup{} changed in both; yours were kept",
+                            n,
+                            if n == 1 { " was" } else { "s were" }
+                        );
+                        return Ok(true);
+                    }
+                    None => {
+                        eprintln!("Both changed the maps or abilities, so they cannot be merged.");
+                        question = "[o]verwrite their changes or [q]uit without saving?";
+                    }
+                }
+            }
+            (Some('o'), _, _) => return Ok(true),
+            (Some('m'), _, _) => {}
+            _ => return Ok(false),
+        }
+    }
+}
+
+fn prompt(question: &str) -> Result<Option<char>> {
+    eprint!("{} ", question);
+    stderr().flush()?;
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    Ok(answer.trim().chars().next().map(|c| c.to_ascii_lowercase()))
+}
+
+fn save(app: &App, save_loc: &SaveLocation) -> Result<()> {
+    write(app, save_loc)?;
+    save_loc.journal().clear()
+}
+
+// Saves without clearing the journal, which may belong to a tracker with the file open
+fn write(app: &App, save_loc: &SaveLocation) -> Result<()> {
+    save_loc.write(|w| Ok(app.save(w)?))
+}
+
+fn restore_backup(save_loc: &SaveLocation, idx: Option<usize>) -> Result<()> {
+    let backups = save_loc.list_backups()?;
+    let idx = match idx {
+        Some(idx) => idx,
+        None => {
+            if backups.is_empty() {
+                println!("No backups of {}", save_loc.path.display());
+            }
+            for (i, backup) in backups.iter().enumerate() {
+                println!("{}: {}", i + 1, backup.taken.format("%Y-%m-%d %H:%M:%S"));
+            }
+            return Ok(());
+        }
+    };
+
+    let _lock = save_loc.lock()?.ok_or_else(|| in_use(save_loc))?;
+    let _writing = save_loc.lock_for_writing()?;
+    let backup = idx
+        .checked_sub(1)
+        .and_then(|i| backups.get(i))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("no backup {}", idx)))?;
+    App::load(BufReader::new(File::open(&backup.path)?)).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("backup {} is unreadable: {}", idx, e),
+        )
+    })?;
+    save_loc.restore(backup)?;
+    println!(
+        "Restored {} from the backup taken {}",
+        save_loc.path.display(),
+        backup.taken.format("%Y-%m-%d %H:%M:%S")
+    );
+    Ok(())
+}
+
+fn list_profiles() -> Result<()> {
+    for (profile, path) in SaveLocation::profiles()? {
+        let title = File::open(&path)
+            .ok()
+            .and_then(|f| App::load(BufReader::new(f)).ok())
+            .map(|app| app.progress.name);
+        match title {
+            Some(title) if title != profile => println!("{} ({})", profile, title),
+            _ => println!("{}", profile),
+        }
+    }
+    Ok(())
+}
+
+fn run_command(cmd: Command, save_loc: &SaveLocation) -> Result<()> {
+    let config = Config::load()?;
+
+    // Commands may run while a tracker has the file open, which then reloads what they change.
+    // Recovering and replaying its unsaved changes is left to that tracker.
+    let modifies = cmd.modifies();
+    let (_writing, lock) = match modifies {
+        true => (Some(save_loc.lock_for_writing()?), save_loc.lock()?),
+        false => (None, None),
+    };
+    let mut app = match lock {
+        Some(_) => load(save_loc)?,
+        None => peek(save_loc)?,
+    };
+    app.configure(config);
+    if let Some(out) = cmd.apply(&mut app.progress)? {
+        println!("{}", out);
+    }
+    match (modifies, lock) {
+        (true, Some(_)) => save(&app, save_loc),
+        (true, None) => write(&app, save_loc),
+        (false, _) => Ok(()),
+    }
+}
+
+fn run_interactive(save_loc: &SaveLocation) -> Result<()> {
+    let config = Config::load()?;
+    let lock = save_loc.lock()?;
+    let read_only = lock.is_none();
+    if read_only {
+        eprintln!("{} is open in another tracker.", save_loc.path.display());
+        if prompt("Open it [r]ead-only or [q]uit?")? != Some('r') {
+            return Err(in_use(save_loc));
+        }
+    }
+
+    let (mut app, seen) = {
+        let _writing = save_loc.lock_for_writing()?;
+        let app = if read_only {
+            peek(save_loc)?
+        } else {
+            load(save_loc)?
+        };
+        (app, RefCell::new(fs::read(&save_loc.path).ok()))
+    };
+    app.configure(config);
+    match read_only {
+        true => app.open_read_only(),
+        false => app.journal_to(save_loc.journal()),
+    }
+    let changed = Arc::new(AtomicBool::new(false));
+    let _watcher = save_loc
+        .watch(Arc::clone(&changed))
+        .map_err(|e| {
+            eprintln!(
+                "lineup-tracker: changes made elsewhere will not be shown: {}",
+                e
+            )
+        })
+        .ok();
+
+    // A second signal kills the tracker outright, in case it has stopped responding.
+    let stop = Arc::new(AtomicBool::new(false));
+    for sig in STOP_SIGNALS {
+        flag::register_conditional_shutdown(*sig, 1, Arc::clone(&stop))?;
+        flag::register(*sig, Arc::clone(&stop))?;
+    }
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        default_hook(info);
+    }));
 
-fn main() -> Result<()> {
     enable_raw_mode()?;
-    let save_loc: String = tilde(SAVE_LOC).into();
 
     let mut stdout = stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = match File::open(&save_loc) {
-        Ok(f) => {
-            let r = BufReader::new(f);
-            App::load(r)?
-        }
-        Err(_) => App::new(),
-    };
-    let res = app.run(&mut terminal);
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        app.run(
+            &mut terminal,
+            &stop,
+            &changed,
+            |app| save_seen(app, save_loc, &mut seen.borrow_mut()),
+            |progress| reload(progress, save_loc, &mut seen.borrow_mut()),
+        )
+    }));
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    restore_terminal()?;
 
-    if let Ok(FinalAction::Save) = res {
-        let f = File::create(&save_loc)?;
-        app.save(BufWriter::new(f))?;
+    // Everything on disk belongs to the tracker which has the file open
+    if read_only {
+        return match res {
+            Ok(Ok(FinalAction::Interrupt)) => {
+                Err(Error::new(ErrorKind::Interrupted, "interrupted"))
+            }
+            Ok(res) => res.map(|_| ()),
+            Err(panic) => panic::resume_unwind(panic),
+        };
     }
 
-    res.map(|_| ())
+    match res {
+        Ok(Ok(FinalAction::Save)) => {
+            let mut seen = seen.into_inner();
+            loop {
+                let writing = save_loc.lock_for_writing()?;
+                let disk = fs::read(&save_loc.path).ok();
+                if disk == seen {
+                    return save(&app, save_loc);
+                }
+                // Nothing else should have to wait on the answer
+                drop(writing);
+                // A signal at the prompt stops the tracker outright rather than waiting on the
+                // answer, leaving what was not saved in the journal to be replayed next time
+                stop.store(true, Ordering::Relaxed);
+                if !merge(&mut app, save_loc, &seen, &disk)? {
+                    rescue(&app, save_loc);
+                    return Ok(());
+                }
+                seen = disk;
+            }
+        }
+        Ok(Ok(FinalAction::None)) => save_loc.journal().clear(),
+        Ok(Ok(FinalAction::Interrupt)) => {
+            rescue(&app, save_loc);
+            Err(Error::new(ErrorKind::Interrupted, "interrupted"))
+        }
+        Ok(Err(e)) => {
+            rescue(&app, save_loc);
+            Err(e)
+        }
+        Err(panic) => {
+            rescue(&app, save_loc);
+            panic::resume_unwind(panic)
+        }
+    }
 }
@@ -0,0 +1,274 @@
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+pub const CURRENT_VERSION: u64 = 2;
+
+type Migration = fn(&mut Map<String, Value>);
+
+// The migration at index i upgrades a document from version i to version i + 1.
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [v0_to_v1, v1_to_v2];
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Damaged(serde_json::Error),
+    Unsupported(u64),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => e.fmt(f),
+            LoadError::Damaged(e) => e.fmt(f),
+            LoadError::Unsupported(v) => write!(
+                f,
+                "save format version {} is newer than the latest supported ({}), please upgrade",
+                v, CURRENT_VERSION
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for LoadError {
+    fn from(e: serde_json::Error) -> Self {
+        LoadError::Damaged(e)
+    }
+}
+
+impl From<LoadError> for io::Error {
+    fn from(e: LoadError) -> Self {
+        match e {
+            LoadError::Io(e) => e,
+            LoadError::Damaged(e) => e.into(),
+            LoadError::Unsupported(_) => io::Error::new(io::ErrorKind::Unsupported, e.to_string()),
+        }
+    }
+}
+
+pub fn version(doc: &Value) -> u64 {
+    doc.get("version").and_then(Value::as_u64).unwrap_or(0)
+}
+
+pub fn migrate(mut doc: Value) -> Result<Value, LoadError> {
+    let from = version(&doc);
+    if from > CURRENT_VERSION {
+        return Err(LoadError::Unsupported(from));
+    }
+
+    if let Value::Object(fields) = &mut doc {
+        for (v, migration) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+            migration(fields);
+            fields.insert("version".into(), (v as u64 + 1).into());
+        }
+    }
+    Ok(doc)
+}
+
+// Version 0 saves predate the version field but are otherwise identical to version 1.
+fn v0_to_v1(_: &mut Map<String, Value>) {}
+
+// Version 2 gives every map, zone, ability and usage an ID and keys progress by IDs rather than names.
+fn v1_to_v2(doc: &mut Map<String, Value>) {
+    let Some(Value::Object(store)) = doc.get_mut("progress") else {
+        return;
+    };
+
+    let mut next_id = 0;
+    let maps = assign_ids(store.get_mut("maps"), "zones", &mut next_id);
+    let abilities = assign_ids(store.get_mut("abilities"), "usages", &mut next_id);
+
+    let mut old_progress = HashMap::new();
+    if let Some(Value::Array(entries)) = store.get_mut("progress") {
+        for entry in entries.drain(..) {
+            if let Ok((names, target)) = serde_json::from_value::<([String; 4], Value)>(entry) {
+                old_progress.insert(names, target);
+            }
+        }
+    }
+
+    // Entries are looked up by name so lineups which shared a key keep sharing their progress
+    let mut progress = Vec::new();
+    for map in &maps {
+        for zone in &map.children {
+            for ability in &abilities {
+                for usage in &ability.children {
+                    let names =
+                        [&map.name, &zone.name, &ability.name, &usage.name].map(String::clone);
+                    if let Some(target) = old_progress.get(&names) {
+                        progress.push(json!([[map.id, zone.id, ability.id, usage.id], target]));
+                    }
+                }
+            }
+        }
+    }
+
+    store.insert("progress".into(), progress.into());
+    store.insert("next_id".into(), next_id.into());
+}
+
+struct Assigned {
+    name: String,
+    id: u64,
+    children: Vec<Assigned>,
+}
+
+fn assign_ids(items: Option<&mut Value>, children: &str, next_id: &mut u64) -> Vec<Assigned> {
+    let Some(Value::Array(items)) = items else {
+        return Vec::new();
+    };
+
+    let mut assigned = Vec::new();
+    for item in items.iter_mut().filter_map(Value::as_object_mut) {
+        let id = fresh_id(next_id);
+        item.insert("id".into(), id.into());
+
+        let mut assigned_children = Vec::new();
+        if let Some(Value::Array(children)) = item.get_mut(children) {
+            for child in children.iter_mut().filter_map(Value::as_object_mut) {
+                let child_id = fresh_id(next_id);
+                child.insert("id".into(), child_id.into());
+                assigned_children.push(Assigned {
+                    name: name_of(child),
+                    id: child_id,
+                    children: Vec::new(),
+                });
+            }
+        }
+        assigned.push(Assigned {
+            name: name_of(item),
+            id,
+            children: assigned_children,
+        });
+    }
+    assigned
+}
+
+fn fresh_id(next_id: &mut u64) -> u64 {
+    let id = *next_id;
+    *next_id += 1;
+    id
+}
+
+fn name_of(item: &Map<String, Value>) -> String {
+    item.get("name")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::App;
+    use crate::selection::{Selection, Selector};
+
+    const V0: &str = include_str!("../tests/fixtures/save-v0.json");
+    const V1: &str = include_str!("../tests/fixtures/save-v1.json");
+    const V2: &str = include_str!("../tests/fixtures/save-v2.json");
+
+    fn check_fixture_contents(app: &App) {
+        let store = &app.progress;
+        assert_eq!(store.name, "Progress");
+        assert_eq!(store.maps.len(), 2);
+        assert_eq!(store.maps[0].name, "Haven");
+        assert_eq!(store.maps[0].zones.len(), 2);
+        assert_eq!(store.maps[1].name, "Bind");
+        assert_eq!(store.abilities.len(), 1);
+        assert_eq!(store.abilities[0].usages.len(), 2);
+        assert_eq!(store.progress.len(), 6);
+
+        let target = |map: &str, zone: &str, ability: &str, usage: &str| {
+            let sel = Selection {
+                map: Some(Selector::Name(map.into())),
+                zone: Some(Selector::Name(zone.into())),
+                ability: Some(Selector::Name(ability.into())),
+                usage: Some(Selector::Name(usage.into())),
+            };
+            let t = &store.progress[&store.key(&sel).unwrap()];
+            (t.progress, t.target)
+        };
+        assert_eq!(target("Haven", "A Main", "Smoke", "One-way"), (3, 4));
+        assert_eq!(target("Haven", "Garage", "Smoke", "Default"), (2, 2));
+        assert_eq!(target("Bind", "Hookah", "Smoke", "One-way"), (0, 0));
+    }
+
+    fn check_ids_unique(app: &App) {
+        let store = &app.progress;
+        let mut ids: Vec<_> = store
+            .maps
+            .iter()
+            .flat_map(|m| m.zones.iter().map(|z| z.id).chain([m.id]))
+            .chain(
+                store
+                    .abilities
+                    .iter()
+                    .flat_map(|a| a.usages.iter().map(|u| u.id).chain([a.id])),
+            )
+            .collect();
+        let count = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), count);
+    }
+
+    #[test]
+    fn migrates_v0() {
+        let doc = migrate(serde_json::from_str(V0).unwrap()).unwrap();
+        assert_eq!(version(&doc), CURRENT_VERSION);
+
+        let app = App::load(V0.as_bytes()).unwrap();
+        check_fixture_contents(&app);
+        assert_eq!(app.selection.map, Some(Selector::Name("Haven".into())));
+    }
+
+    #[test]
+    fn migrates_v1() {
+        let doc = migrate(serde_json::from_str(V1).unwrap()).unwrap();
+        assert_eq!(version(&doc), CURRENT_VERSION);
+
+        let app = App::load(V1.as_bytes()).unwrap();
+        check_fixture_contents(&app);
+        check_ids_unique(&app);
+        assert_eq!(app.selection.map, Some(Selector::Index(0)));
+    }
+
+    #[test]
+    fn loads_v2() {
+        let doc: Value = serde_json::from_str(V2).unwrap();
+        assert_eq!(version(&doc), 2);
+
+        let app = App::load(V2.as_bytes()).unwrap();
+        check_fixture_contents(&app);
+        check_ids_unique(&app);
+    }
+
+    #[test]
+    fn saves_current_version() {
+        let mut buf = Vec::new();
+        App::load(V0.as_bytes()).unwrap().save(&mut buf).unwrap();
+
+        let doc: Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(version(&doc), CURRENT_VERSION);
+        check_fixture_contents(&App::load(buf.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let doc = serde_json::json!({ "version": CURRENT_VERSION + 1 });
+        assert!(matches!(migrate(doc), Err(LoadError::Unsupported(_))));
+
+        let data = format!(r#"{{"version":{}}}"#, CURRENT_VERSION + 1);
+        assert!(matches!(
+            App::load(data.as_bytes()),
+            Err(LoadError::Unsupported(_))
+        ));
+    }
+}
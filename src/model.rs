@@ -1,126 +1,238 @@
 use crate::selection::{Selection, Selector};
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::{Deserialize as Deserialise, Serialize as Serialise};
+use serde_json::{json, Value};
 use serde_with::serde_as;
 use std::cmp::max;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use std::hash::{Hash, Hasher};
+use std::io::{self, ErrorKind};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, PartialEq)]
+pub enum ModelError {
+    Unknown(&'static str, String),
+    UnknownId(Id),
+    Duplicate(&'static str, String),
+    InvalidName(String, &'static str),
+    OutOfRange(&'static str, usize),
+    NoSelection(&'static str),
+}
+
+impl ModelError {
+    fn missing(sel: &Selector, what: &'static str) -> Self {
+        match sel {
+            Selector::Name(name) => ModelError::Unknown(what, name.clone()),
+            Selector::Index(idx) => ModelError::OutOfRange(what, *idx),
+        }
+    }
+}
+
+impl Display for ModelError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ModelError::Unknown(what, name) => write!(f, "no {} named '{}'", what, name),
+            ModelError::UnknownId(id) => write!(f, "nothing has ID {}", id.0),
+            ModelError::Duplicate(what, name) => {
+                write!(f, "there is already a {} named '{}'", what, name)
+            }
+            ModelError::InvalidName(name, why) => write!(f, "{}, got '{}'", why, name),
+            ModelError::OutOfRange(what, idx) => write!(f, "no {} at index {}", what, idx),
+            ModelError::NoSelection(what) => write!(f, "no {} is selected", what),
+        }
+    }
+}
+
+impl Error for ModelError {}
+
+impl From<ModelError> for io::Error {
+    fn from(e: ModelError) -> Self {
+        let kind = match e {
+            ModelError::Unknown(..) | ModelError::UnknownId(_) | ModelError::OutOfRange(..) => {
+                ErrorKind::NotFound
+            }
+            ModelError::Duplicate(..) => ErrorKind::AlreadyExists,
+            ModelError::InvalidName(..) | ModelError::NoSelection(_) => ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, e.to_string())
+    }
+}
+
+lazy_static! {
+    static ref NUM_RE: Regex = Regex::new("^[0-9]+$").unwrap();
+}
+
+// Numbers are reserved for selecting by index
+pub fn check_name(name: &str) -> Result<(), ModelError> {
+    if name.trim().is_empty() {
+        return Err(ModelError::InvalidName(
+            name.into(),
+            "names cannot be blank",
+        ));
+    }
+    if NUM_RE.is_match(name) {
+        return Err(ModelError::InvalidName(
+            name.into(),
+            "names cannot be numbers",
+        ));
+    }
+    Ok(())
+}
 
 pub trait Nameable {
-    fn name<'a>(&'a self) -> &'a String;
+    fn name(&self) -> &String;
+}
+
+pub trait Identifiable {
+    fn id(&self) -> Id;
 }
 
-#[derive(Debug, Eq, Serialise, Deserialise)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialise, Deserialise)]
+#[serde(transparent)]
+pub struct Id(u32);
+
+// Progress is keyed by the IDs of the map, zone, ability and usage of each lineup
+pub type LineupKey = (Id, Id, Id, Id);
+
+#[derive(Clone, Debug, Eq, Serialise, Deserialise)]
 pub struct Map {
+    pub id: Id,
     pub name: String,
     pub zones: Vec<Zone>,
 }
 
 impl Map {
-    pub fn new(name: String) -> Self {
+    pub fn new(id: Id, name: String) -> Self {
         Map {
-            name: name,
+            id,
+            name,
             zones: Vec::new(),
         }
     }
-
-    pub fn add_zone(&mut self, z: Zone) {
-        self.zones.push(z)
-    }
 }
 
 impl PartialEq for Map {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
+        self.id == other.id
     }
 }
 
 impl Hash for Map {
     fn hash<H: Hasher>(&self, h: &mut H) {
-        self.name.hash(h);
+        self.id.hash(h);
     }
 }
 
 impl Nameable for Map {
-    fn name<'a>(&'a self) -> &'a String {
+    fn name(&self) -> &String {
         &self.name
     }
 }
 
-#[derive(Debug, Eq, Hash, PartialEq, Serialise, Deserialise)]
+impl Identifiable for Map {
+    fn id(&self) -> Id {
+        self.id
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialise, Deserialise)]
 pub struct Zone {
+    pub id: Id,
     pub name: String,
 }
 
 impl Zone {
-    pub fn new(name: String) -> Self {
-        Zone { name: name }
+    pub fn new(id: Id, name: String) -> Self {
+        Zone { id, name }
     }
 }
 
 impl Nameable for Zone {
-    fn name<'a>(&'a self) -> &'a String {
+    fn name(&self) -> &String {
         &self.name
     }
 }
 
-#[derive(Eq, Debug, Serialise, Deserialise)]
+impl Identifiable for Zone {
+    fn id(&self) -> Id {
+        self.id
+    }
+}
+
+#[derive(Clone, Eq, Debug, Serialise, Deserialise)]
 pub struct Ability {
+    pub id: Id,
     pub name: String,
     pub usages: Vec<Usage>,
 }
 
 impl Ability {
-    pub fn new(name: String) -> Self {
+    pub fn new(id: Id, name: String) -> Self {
         Ability {
-            name: name,
+            id,
+            name,
             usages: Vec::new(),
         }
     }
-
-    pub fn add_usage(&mut self, u: Usage) {
-        self.usages.push(u);
-    }
 }
 
 impl PartialEq for Ability {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
+        self.id == other.id
     }
 }
 
 impl Nameable for Ability {
-    fn name<'a>(&'a self) -> &'a String {
+    fn name(&self) -> &String {
         &self.name
     }
 }
 
+impl Identifiable for Ability {
+    fn id(&self) -> Id {
+        self.id
+    }
+}
+
 impl Hash for Ability {
     fn hash<H: Hasher>(&self, h: &mut H) {
-        self.name.hash(h);
+        self.id.hash(h);
     }
 }
 
-#[derive(Eq, Hash, PartialEq, Debug, Serialise, Deserialise)]
+#[derive(Clone, Eq, Hash, PartialEq, Debug, Serialise, Deserialise)]
 pub struct Usage {
+    pub id: Id,
     pub name: String,
 }
 
 impl Usage {
-    pub fn new(name: String) -> Usage {
-        Usage { name: name }
+    pub fn new(id: Id, name: String) -> Usage {
+        Usage { id, name }
     }
 }
 
 impl Nameable for Usage {
-    fn name<'a>(&'a self) -> &'a String {
+    fn name(&self) -> &String {
         &self.name
     }
 }
 
-#[derive(Debug, Serialise, Deserialise)]
+impl Identifiable for Usage {
+    fn id(&self) -> Id {
+        self.id
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialise, Deserialise)]
 pub struct Target {
     pub progress: i32,
     pub target: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_practised: Option<u64>,
 }
 
 impl Target {
@@ -133,7 +245,7 @@ impl Target {
     }
 
     pub fn match_progress_to_target(&mut self) {
-        self.progress = self.target;
+        self.set_progress(self.target);
     }
 
     pub fn match_target_to_progress(&mut self) {
@@ -141,11 +253,25 @@ impl Target {
     }
 
     pub fn change_progress(&mut self, delta: i32) {
-        self.progress += delta;
+        self.set_progress(self.progress.saturating_add(delta));
     }
 
     pub fn change_target(&mut self, delta: i32) {
-        self.target = max(self.target + delta, 0);
+        self.target = max(self.target.saturating_add(delta), 0);
+    }
+
+    pub fn set_progress(&mut self, progress: i32) {
+        if progress > self.progress {
+            self.last_practised = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs());
+        }
+        self.progress = progress;
+    }
+
+    pub fn set_target(&mut self, target: i32) {
+        self.target = max(target, 0);
     }
 }
 
@@ -154,190 +280,956 @@ impl Default for Target {
         Target {
             progress: 0,
             target: 2,
+            last_practised: None,
         }
     }
 }
 
+#[derive(Clone, Copy)]
+pub enum SortKey {
+    Name,
+    Completion,
+    Recent,
+}
+
+#[derive(Default)]
+struct Stats {
+    progress: i32,
+    target: i32,
+    last_practised: Option<u64>,
+}
+
+impl Stats {
+    fn add(&mut self, t: &Target) {
+        // Targets can be driven below zero by matching them to negative progress
+        let target = t.target.max(0);
+        self.progress += t.progress.clamp(0, target);
+        self.target += target;
+        self.last_practised = max(self.last_practised, t.last_practised);
+    }
+
+    fn completion(&self) -> f64 {
+        if self.target == 0 {
+            1.0
+        } else {
+            self.progress as f64 / self.target as f64
+        }
+    }
+}
+
+// A map, zone, ability or usage with where it sits, enough to put it back after removal
+#[derive(Clone, Debug)]
+pub enum Item {
+    Map {
+        index: usize,
+        map: Map,
+    },
+    Zone {
+        map: Id,
+        index: usize,
+        zone: Zone,
+    },
+    Ability {
+        index: usize,
+        ability: Ability,
+    },
+    Usage {
+        ability: Id,
+        index: usize,
+        usage: Usage,
+    },
+}
+
+impl Item {
+    pub fn id(&self) -> Id {
+        match self {
+            Item::Map { map, .. } => map.id,
+            Item::Zone { zone, .. } => zone.id,
+            Item::Ability { ability, .. } => ability.id,
+            Item::Usage { usage, .. } => usage.id,
+        }
+    }
+
+    pub fn name(&self) -> &String {
+        match self {
+            Item::Map { map, .. } => &map.name,
+            Item::Zone { zone, .. } => &zone.name,
+            Item::Ability { ability, .. } => &ability.name,
+            Item::Usage { usage, .. } => &usage.name,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Item::Map { map, .. } => format!("map '{}'", map.name),
+            Item::Zone { zone, .. } => format!("zone '{}'", zone.name),
+            Item::Ability { ability, .. } => format!("ability '{}'", ability.name),
+            Item::Usage { usage, .. } => format!("usage '{}'", usage.name),
+        }
+    }
+}
+
+// The order of maps with their zones and abilities with their usages
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arrangement {
+    maps: Vec<(Id, Vec<Id>)>,
+    abilities: Vec<(Id, Vec<Id>)>,
+}
+
+pub struct Selected<'a> {
+    pub map: Option<&'a Map>,
+    pub zone: Option<&'a Zone>,
+    pub ability: Option<&'a Ability>,
+    pub usage: Option<&'a Usage>,
+}
+
 #[serde_as]
 #[derive(Serialise, Deserialise)]
 pub struct ProgressStore {
     pub name: String,
+    next_id: u32,
     pub maps: Vec<Map>,
     pub abilities: Vec<Ability>,
+    // Ordered so that saving an unchanged store gives the same bytes
     #[serde_as(as = "Vec<(_,_)>")]
-    pub progress: HashMap<(String, String, String, String), Target>,
+    pub progress: BTreeMap<LineupKey, Target>,
+    // Whether names which differ only in case count as duplicates
+    #[serde(skip)]
+    pub case_insensitive_names: bool,
 }
 
 impl ProgressStore {
     pub fn new(name: String) -> Self {
         ProgressStore {
-            name: name,
+            name,
+            next_id: 0,
             maps: Vec::new(),
             abilities: Vec::new(),
-            progress: HashMap::new(),
+            progress: BTreeMap::new(),
+            case_insensitive_names: false,
         }
     }
 
-    pub fn add_map(&mut self, m: Map) {
-        self.maps.push(m);
+    pub fn salvage(v: &Value, default_name: &str, losses: &mut Vec<String>) -> Self {
+        let name = match v.get("name").and_then(Value::as_str) {
+            Some(name) => name.to_string(),
+            None => {
+                losses.push("the progress store name".into());
+                default_name.to_string()
+            }
+        };
+
+        let maps = salvage_list(v.get("maps"), "maps", losses, |m, losses| {
+            let id = Id::deserialize(m.get("id")?).ok()?;
+            let name = m.get("name")?.as_str()?.to_string();
+            let zones = salvage_list(
+                m.get("zones"),
+                &format!("zones of {}", name),
+                losses,
+                |z, _| Zone::deserialize(z).ok(),
+            );
+            Some(Map { id, name, zones })
+        });
+        let abilities = salvage_list(v.get("abilities"), "abilities", losses, |a, losses| {
+            let id = Id::deserialize(a.get("id")?).ok()?;
+            let name = a.get("name")?.as_str()?.to_string();
+            let usages = salvage_list(
+                a.get("usages"),
+                &format!("usages of {}", name),
+                losses,
+                |u, _| Usage::deserialize(u).ok(),
+            );
+            Some(Ability { id, name, usages })
+        });
+        let entries: Vec<(LineupKey, Target)> =
+            salvage_list(v.get("progress"), "progress entries", losses, |e, _| {
+                Deserialise::deserialize(e).ok()
+            });
+
+        let mut store = ProgressStore {
+            name,
+            next_id: 0,
+            maps,
+            abilities,
+            progress: entries.into_iter().collect(),
+            case_insensitive_names: false,
+        };
+        store.next_id = store.max_id().map_or(0, |Id(id)| id + 1);
+        store.reconcile(losses);
+        store
+    }
+
+    fn max_id(&self) -> Option<Id> {
+        let map_ids = self
+            .maps
+            .iter()
+            .flat_map(|m| std::iter::once(m.id).chain(m.zones.iter().map(|z| z.id)));
+        let ability_ids = self
+            .abilities
+            .iter()
+            .flat_map(|a| std::iter::once(a.id).chain(a.usages.iter().map(|u| u.id)));
+        map_ids.chain(ability_ids).max()
     }
 
-    pub fn add_zone(&mut self, map_sel: &Selector, zone_sel: &Selector) {
-        let mo = map_sel.get_selected(&self.maps);
-        if mo == None {
-            return;
+    fn reconcile(&mut self, losses: &mut Vec<String>) {
+        let mut progress = BTreeMap::new();
+        let mut missing = 0;
+        for key in self.keys() {
+            let target = self.progress.remove(&key).unwrap_or_else(|| {
+                missing += 1;
+                Target::default()
+            });
+            progress.insert(key, target);
+        }
+
+        if missing != 0 {
+            losses.push(format!(
+                "progress of {} lineups (reset to default)",
+                missing
+            ));
         }
-        let m = mo.unwrap();
-        let zo = zone_sel.get_selected(&m.zones);
-        if zo == None {
-            return;
+        if !self.progress.is_empty() {
+            losses.push(format!(
+                "{} progress entries for lineups which no longer exist",
+                self.progress.len()
+            ));
         }
-        let z = zo.unwrap();
+        self.progress = progress;
+    }
 
-        for a in &self.abilities {
-            for u in &a.usages {
-                self.progress.insert(
-                    (
-                        m.name.clone(),
-                        z.name.clone(),
-                        a.name.clone(),
-                        u.name.clone(),
-                    ),
-                    Target::default(),
-                );
+    fn keys(&self) -> Vec<LineupKey> {
+        let mut keys = Vec::new();
+        for m in &self.maps {
+            for z in &m.zones {
+                for a in &self.abilities {
+                    for u in &a.usages {
+                        keys.push((m.id, z.id, a.id, u.id));
+                    }
+                }
             }
         }
+        keys
     }
 
-    pub fn add_ability(&mut self, a: Ability) {
-        self.abilities.push(a);
+    fn fresh_id(&mut self) -> Id {
+        let id = Id(self.next_id);
+        self.next_id += 1;
+        id
     }
 
-    pub fn add_usage(&mut self, ability_sel: &Selector, usage_sel: &Selector) {
-        let ao = ability_sel.get_selected(&self.abilities);
-        if ao == None {
-            return;
+    pub fn add_map(&mut self, name: String) -> Result<Id, ModelError> {
+        self.check_unique(&name, &self.maps, "map", None)?;
+        let id = self.fresh_id();
+        self.maps.push(Map::new(id, name));
+        Ok(id)
+    }
+
+    pub fn add_zone(&mut self, map_sel: &Selector, name: String) -> Result<Id, ModelError> {
+        let m = Self::find(map_sel, &self.maps, "map")?;
+        self.check_unique(&name, &m.zones, "zone", None)?;
+        let map = m.id;
+
+        let id = self.fresh_id();
+        if let Some(m) = self.maps.iter_mut().find(|m| m.id == map) {
+            m.zones.push(Zone::new(id, name));
         }
-        let a = ao.unwrap();
-        let uo = usage_sel.get_selected(&a.usages);
-        if uo == None {
-            return;
+        for a in &self.abilities {
+            for u in &a.usages {
+                self.progress
+                    .insert((map, id, a.id, u.id), Target::default());
+            }
         }
-        let u = uo.unwrap();
+        Ok(id)
+    }
 
+    pub fn add_ability(&mut self, name: String) -> Result<Id, ModelError> {
+        self.check_unique(&name, &self.abilities, "ability", None)?;
+        let id = self.fresh_id();
+        self.abilities.push(Ability::new(id, name));
+        Ok(id)
+    }
+
+    pub fn add_usage(&mut self, ability_sel: &Selector, name: String) -> Result<Id, ModelError> {
+        let a = Self::find(ability_sel, &self.abilities, "ability")?;
+        self.check_unique(&name, &a.usages, "usage", None)?;
+        let ability = a.id;
+
+        let id = self.fresh_id();
+        if let Some(a) = self.abilities.iter_mut().find(|a| a.id == ability) {
+            a.usages.push(Usage::new(id, name));
+        }
         for m in &self.maps {
             for z in &m.zones {
-                self.progress.insert(
-                    (
-                        m.name.clone(),
-                        z.name.clone(),
-                        a.name.clone(),
-                        u.name.clone(),
-                    ),
-                    Target::default(),
-                );
+                self.progress
+                    .insert((m.id, z.id, ability, id), Target::default());
             }
         }
+        Ok(id)
     }
 
-    pub fn get_target(&self, sel: &Selection) -> Option<&Target> {
-        match sel {
-            Selection {
-                map: Some(msel),
-                zone: Some(zsel),
-                ability: Some(asel),
-                usage: Some(usel),
+    pub fn selected(&self, sel: &Selection) -> Selected<'_> {
+        let map = sel.map.as_ref().and_then(|m| m.get_selected(&self.maps));
+        let ability = sel
+            .ability
+            .as_ref()
+            .and_then(|a| a.get_selected(&self.abilities));
+        Selected {
+            map,
+            zone: map.and_then(|m| sel.zone.as_ref()?.get_selected(&m.zones)),
+            ability,
+            usage: ability.and_then(|a| sel.usage.as_ref()?.get_selected(&a.usages)),
+        }
+    }
+
+    // Names the first level which has nothing selected, if any
+    pub fn key(&self, sel: &Selection) -> Result<LineupKey, ModelError> {
+        let selected = self.selected(sel);
+        Ok((
+            selected.map.ok_or(ModelError::NoSelection("map"))?.id,
+            selected.zone.ok_or(ModelError::NoSelection("zone"))?.id,
+            selected
+                .ability
+                .ok_or(ModelError::NoSelection("ability"))?
+                .id,
+            selected.usage.ok_or(ModelError::NoSelection("usage"))?.id,
+        ))
+    }
+
+    pub fn rm_map(&mut self, map: &str) -> Result<(), ModelError> {
+        let id = Self::id_of(map, &self.maps, "map")?;
+        self.delete_item(id)
+    }
+
+    pub fn rm_zone(&mut self, map: &str, zone: &str) -> Result<(), ModelError> {
+        let m = Self::find(&Selector::Name(map.to_string()), &self.maps, "map")?;
+        let id = Self::id_of(zone, &m.zones, "zone")?;
+        self.delete_item(id)
+    }
+
+    pub fn rm_ability(&mut self, ability: &str) -> Result<(), ModelError> {
+        let id = Self::id_of(ability, &self.abilities, "ability")?;
+        self.delete_item(id)
+    }
+
+    pub fn rm_usage(&mut self, ability: &str, usage: &str) -> Result<(), ModelError> {
+        let a = Self::find(
+            &Selector::Name(ability.to_string()),
+            &self.abilities,
+            "ability",
+        )?;
+        let id = Self::id_of(usage, &a.usages, "usage")?;
+        self.delete_item(id)
+    }
+
+    pub fn rename_map(&mut self, map: &str, name: String) -> Result<(), ModelError> {
+        let id = Self::id_of(map, &self.maps, "map")?;
+        self.rename_item(id, &name)
+    }
+
+    pub fn rename_zone(&mut self, map: &str, zone: &str, name: String) -> Result<(), ModelError> {
+        let m = Self::find(&Selector::Name(map.to_string()), &self.maps, "map")?;
+        let id = Self::id_of(zone, &m.zones, "zone")?;
+        self.rename_item(id, &name)
+    }
+
+    pub fn rename_ability(&mut self, ability: &str, name: String) -> Result<(), ModelError> {
+        let id = Self::id_of(ability, &self.abilities, "ability")?;
+        self.rename_item(id, &name)
+    }
+
+    pub fn rename_usage(
+        &mut self,
+        ability: &str,
+        usage: &str,
+        name: String,
+    ) -> Result<(), ModelError> {
+        let a = Self::find(
+            &Selector::Name(ability.to_string()),
+            &self.abilities,
+            "ability",
+        )?;
+        let id = Self::id_of(usage, &a.usages, "usage")?;
+        self.rename_item(id, &name)
+    }
+
+    // Moving past either end does nothing
+    pub fn move_map(&mut self, map: &str, delta: isize) -> Result<(), ModelError> {
+        Self::move_named(map, delta, &mut self.maps, "map")
+    }
+
+    pub fn move_zone(&mut self, map: &str, zone: &str, delta: isize) -> Result<(), ModelError> {
+        let m = Self::find_mut(&Selector::Name(map.to_string()), &mut self.maps, "map")?;
+        Self::move_named(zone, delta, &mut m.zones, "zone")
+    }
+
+    pub fn move_ability(&mut self, ability: &str, delta: isize) -> Result<(), ModelError> {
+        Self::move_named(ability, delta, &mut self.abilities, "ability")
+    }
+
+    pub fn move_usage(
+        &mut self,
+        ability: &str,
+        usage: &str,
+        delta: isize,
+    ) -> Result<(), ModelError> {
+        let a = Self::find_mut(
+            &Selector::Name(ability.to_string()),
+            &mut self.abilities,
+            "ability",
+        )?;
+        Self::move_named(usage, delta, &mut a.usages, "usage")
+    }
+
+    fn move_named<T>(
+        name: &str,
+        delta: isize,
+        vs: &mut [T],
+        what: &'static str,
+    ) -> Result<(), ModelError>
+    where
+        T: Nameable,
+    {
+        let idx = vs
+            .iter()
+            .position(|v| v.name() == name)
+            .ok_or_else(|| ModelError::Unknown(what, name.to_string()))?;
+        if let Some(to) = idx.checked_add_signed(delta).filter(|to| *to < vs.len()) {
+            vs.swap(idx, to);
+        }
+        Ok(())
+    }
+
+    pub fn sort_rows(&mut self, key: SortKey, reverse: bool) {
+        let mut map_stats = HashMap::new();
+        let mut zone_stats = HashMap::new();
+        for ((m, z, _, _), t) in &self.progress {
+            map_stats.entry(*m).or_insert_with(Stats::default).add(t);
+            zone_stats.entry(*z).or_insert_with(Stats::default).add(t);
+        }
+
+        Self::sort_by_stats(&mut self.maps, &map_stats, key, reverse);
+        for m in &mut self.maps {
+            Self::sort_by_stats(&mut m.zones, &zone_stats, key, reverse);
+        }
+    }
+
+    pub fn sort_columns(&mut self, key: SortKey, reverse: bool) {
+        let mut ability_stats = HashMap::new();
+        let mut usage_stats = HashMap::new();
+        for ((_, _, a, u), t) in &self.progress {
+            ability_stats
+                .entry(*a)
+                .or_insert_with(Stats::default)
+                .add(t);
+            usage_stats.entry(*u).or_insert_with(Stats::default).add(t);
+        }
+
+        Self::sort_by_stats(&mut self.abilities, &ability_stats, key, reverse);
+        for a in &mut self.abilities {
+            Self::sort_by_stats(&mut a.usages, &usage_stats, key, reverse);
+        }
+    }
+
+    // Names sort alphabetically, completion sorts least complete first and recency sorts most
+    // recently practised first
+    fn sort_by_stats<T>(vs: &mut [T], stats: &HashMap<Id, Stats>, key: SortKey, reverse: bool)
+    where
+        T: Nameable + Identifiable,
+    {
+        let none = Stats::default();
+        vs.sort_by(|a, b| {
+            let (sa, sb) = (
+                stats.get(&a.id()).unwrap_or(&none),
+                stats.get(&b.id()).unwrap_or(&none),
+            );
+            let ord = match key {
+                SortKey::Name => a.name().to_lowercase().cmp(&b.name().to_lowercase()),
+                SortKey::Completion => sa.completion().total_cmp(&sb.completion()),
+                SortKey::Recent => sb.last_practised.cmp(&sa.last_practised),
+            };
+            if reverse {
+                ord.reverse()
+            } else {
+                ord
+            }
+        });
+    }
+
+    pub fn item(&self, id: Id) -> Option<Item> {
+        for (index, map) in self.maps.iter().enumerate() {
+            if map.id == id {
+                let map = map.clone();
+                return Some(Item::Map { index, map });
+            }
+            if let Some(index) = map.zones.iter().position(|z| z.id == id) {
+                let zone = map.zones[index].clone();
+                return Some(Item::Zone {
+                    map: map.id,
+                    index,
+                    zone,
+                });
+            }
+        }
+        for (index, ability) in self.abilities.iter().enumerate() {
+            if ability.id == id {
+                let ability = ability.clone();
+                return Some(Item::Ability { index, ability });
+            }
+            if let Some(index) = ability.usages.iter().position(|u| u.id == id) {
+                let usage = ability.usages[index].clone();
+                return Some(Item::Usage {
+                    ability: ability.id,
+                    index,
+                    usage,
+                });
+            }
+        }
+        None
+    }
+
+    // Every target of lineups which involve the given map, zone, ability or usage
+    pub fn targets_of(&self, id: Id) -> Vec<(LineupKey, Target)> {
+        self.progress
+            .iter()
+            .filter(|((m, z, a, u), _)| [m, z, a, u].contains(&&id))
+            .map(|(k, t)| (*k, t.clone()))
+            .collect()
+    }
+
+    pub fn insert_item(
+        &mut self,
+        item: &Item,
+        targets: &[(LineupKey, Target)],
+    ) -> Result<(), ModelError> {
+        match item {
+            Item::Map { index, map } => Self::insert_at(&mut self.maps, *index, map.clone()),
+            Item::Zone { map, index, zone } => {
+                let m = self
+                    .maps
+                    .iter_mut()
+                    .find(|m| m.id == *map)
+                    .ok_or(ModelError::UnknownId(*map))?;
+                Self::insert_at(&mut m.zones, *index, zone.clone());
+            }
+            Item::Ability { index, ability } => {
+                Self::insert_at(&mut self.abilities, *index, ability.clone())
+            }
+            Item::Usage {
+                ability,
+                index,
+                usage,
             } => {
-                match (
-                    msel.get_selected(&self.maps),
-                    asel.get_selected(&self.abilities),
-                ) {
-                    (Some(map), Some(ability)) => {
-                        match (
-                            zsel.get_selected(&map.zones),
-                            usel.get_selected(&ability.usages),
-                        ) {
-                            (Some(zone), Some(usage)) => self.progress.get(&(
-                                map.name.clone(),
-                                zone.name.clone(),
-                                ability.name.clone(),
-                                usage.name.clone(),
-                            )),
-                            _ => None,
-                        }
-                    }
-                    _ => None,
-                }
+                let a = self
+                    .abilities
+                    .iter_mut()
+                    .find(|a| a.id == *ability)
+                    .ok_or(ModelError::UnknownId(*ability))?;
+                Self::insert_at(&mut a.usages, *index, usage.clone());
             }
-            _ => None,
         }
+        self.next_id = max(self.next_id, item.id().0 + 1);
+        self.progress.extend(targets.iter().cloned());
+        Ok(())
     }
 
-    pub fn rm_map(&mut self, map: &String) {
-        self.progress.retain(|(mn, _, _, _), _| mn != map);
-        Self::remove_named(map, &mut self.maps);
+    fn insert_at<T>(vs: &mut Vec<T>, index: usize, v: T) {
+        vs.insert(index.min(vs.len()), v);
     }
 
-    pub fn rm_zone(&mut self, map: &String, zone: &String) {
+    pub fn delete_item(&mut self, id: Id) -> Result<(), ModelError> {
+        if self.item(id).is_none() {
+            return Err(ModelError::UnknownId(id));
+        }
         self.progress
-            .retain(|(mn, zn, _, _), _| mn != map || zn != zone);
-        if let Some(m) = Selector::Name(map.clone()).get_selected_mut(&mut self.maps) {
-            Self::remove_named(zone, &mut m.zones);
+            .retain(|(m, z, a, u), _| ![m, z, a, u].contains(&&id));
+        self.maps.retain(|m| m.id != id);
+        for m in &mut self.maps {
+            m.zones.retain(|z| z.id != id);
         }
+        self.abilities.retain(|a| a.id != id);
+        for a in &mut self.abilities {
+            a.usages.retain(|u| u.id != id);
+        }
+        Ok(())
     }
 
-    pub fn rm_ability(&mut self, ability: &String) {
-        self.progress.retain(|(_, _, an, _), _| an != ability);
-        Self::remove_named(ability, &mut self.abilities);
+    pub fn rename_item(&mut self, id: Id, name: &str) -> Result<(), ModelError> {
+        match self.item(id).ok_or(ModelError::UnknownId(id))? {
+            Item::Map { .. } => self.check_unique(name, &self.maps, "map", Some(id))?,
+            Item::Zone { map, .. } => {
+                let m = self.maps.iter().find(|m| m.id == map);
+                let zones = m.map_or(&[][..], |m| &m.zones);
+                self.check_unique(name, zones, "zone", Some(id))?
+            }
+            Item::Ability { .. } => {
+                self.check_unique(name, &self.abilities, "ability", Some(id))?
+            }
+            Item::Usage { ability, .. } => {
+                let a = self.abilities.iter().find(|a| a.id == ability);
+                let usages = a.map_or(&[][..], |a| &a.usages);
+                self.check_unique(name, usages, "usage", Some(id))?
+            }
+        }
+
+        let names = self
+            .maps
+            .iter_mut()
+            .flat_map(|m| {
+                let zones = m.zones.iter_mut().map(|z| (z.id, &mut z.name));
+                [(m.id, &mut m.name)].into_iter().chain(zones)
+            })
+            .chain(self.abilities.iter_mut().flat_map(|a| {
+                let usages = a.usages.iter_mut().map(|u| (u.id, &mut u.name));
+                [(a.id, &mut a.name)].into_iter().chain(usages)
+            }));
+        for (item_id, item_name) in names {
+            if item_id == id {
+                *item_name = name.to_string();
+            }
+        }
+        Ok(())
     }
 
-    pub fn rm_usage(&mut self, ability: &String, usage: &String) {
-        self.progress
-            .retain(|(_, _, an, un), _| an != ability || un != usage);
-        if let Some(a) = Selector::Name(ability.clone()).get_selected_mut(&mut self.abilities) {
-            Self::remove_named(usage, &mut a.usages);
+    // Only lineups which exist can be given targets, so stale keys cannot leave orphaned progress
+    pub fn set_target_of(&mut self, key: LineupKey, target: Target) -> Result<(), ModelError> {
+        let Some(t) = self.progress.get_mut(&key) else {
+            let (m, z, a, u) = key;
+            let missing = [m, z, a, u].into_iter().find(|id| self.item(*id).is_none());
+            return Err(ModelError::UnknownId(missing.unwrap_or(z)));
+        };
+        *t = target;
+        Ok(())
+    }
+
+    // Takes in changes made to another copy of the store since both were the same as the base.
+    // Lineups changed in both keep the progress from this copy, and are counted. Returns None,
+    // changing nothing, if both copies have changed their maps or abilities.
+    pub fn merge(&mut self, base: &ProgressStore, theirs: &ProgressStore) -> Option<usize> {
+        let relayout = if self.layout() == base.layout() {
+            true
+        } else if theirs.layout() == base.layout() {
+            false
+        } else {
+            return None;
+        };
+
+        let mut progress = match relayout {
+            true => theirs.progress.clone(),
+            false => self.progress.clone(),
+        };
+        let mut conflicts = 0;
+        for (key, target) in progress.iter_mut() {
+            let ours = self.progress.get(key);
+            let original = base.progress.get(key);
+            let changed = theirs.progress.get(key);
+            if ours == original {
+                if let Some(changed) = changed {
+                    *target = changed.clone();
+                }
+            } else if let Some(ours) = ours {
+                if changed != original && changed != Some(ours) {
+                    conflicts += 1;
+                }
+                *target = ours.clone();
+            }
         }
+
+        if relayout {
+            self.name = theirs.name.clone();
+            self.maps = theirs.maps.clone();
+            self.abilities = theirs.abilities.clone();
+        }
+        self.next_id = max(self.next_id, theirs.next_id);
+        self.progress = progress;
+        Some(conflicts)
+    }
+
+    fn layout(&self) -> Value {
+        json!([self.name, self.maps, self.abilities])
     }
 
-    fn remove_named<T>(name: &String, vs: &mut Vec<T>)
+    pub fn arrangement(&self) -> Arrangement {
+        Arrangement {
+            maps: self
+                .maps
+                .iter()
+                .map(|m| (m.id, m.zones.iter().map(|z| z.id).collect()))
+                .collect(),
+            abilities: self
+                .abilities
+                .iter()
+                .map(|a| (a.id, a.usages.iter().map(|u| u.id).collect()))
+                .collect(),
+        }
+    }
+
+    // Anything not mentioned in the arrangement is kept after everything which is
+    pub fn arrange(&mut self, arrangement: &Arrangement) {
+        fn position(order: &[Id], id: Id) -> usize {
+            order.iter().position(|o| *o == id).unwrap_or(order.len())
+        }
+
+        let map_order: Vec<_> = arrangement.maps.iter().map(|(m, _)| *m).collect();
+        self.maps.sort_by_key(|m| position(&map_order, m.id));
+        for (id, zone_order) in &arrangement.maps {
+            if let Some(m) = self.maps.iter_mut().find(|m| m.id == *id) {
+                m.zones.sort_by_key(|z| position(zone_order, z.id));
+            }
+        }
+
+        let ability_order: Vec<_> = arrangement.abilities.iter().map(|(a, _)| *a).collect();
+        self.abilities
+            .sort_by_key(|a| position(&ability_order, a.id));
+        for (id, usage_order) in &arrangement.abilities {
+            if let Some(a) = self.abilities.iter_mut().find(|a| a.id == *id) {
+                a.usages.sort_by_key(|u| position(usage_order, u.id));
+            }
+        }
+    }
+
+    fn id_of<T>(name: &str, vs: &[T], what: &'static str) -> Result<Id, ModelError>
+    where
+        T: Nameable + Identifiable,
+    {
+        vs.iter()
+            .find(|v| v.name() == name)
+            .map(|v| v.id())
+            .ok_or_else(|| ModelError::Unknown(what, name.to_string()))
+    }
+
+    fn find<'a, T>(sel: &Selector, vs: &'a [T], what: &'static str) -> Result<&'a T, ModelError>
     where
         T: Nameable,
     {
-        if let Some(idx) = vs.iter().position(|v| v.name() == name) {
-            vs.remove(idx);
+        sel.get_selected(vs)
+            .ok_or_else(|| ModelError::missing(sel, what))
+    }
+
+    fn find_mut<'a, T>(
+        sel: &Selector,
+        vs: &'a mut [T],
+        what: &'static str,
+    ) -> Result<&'a mut T, ModelError>
+    where
+        T: Nameable,
+    {
+        sel.get_selected_mut(vs)
+            .ok_or_else(|| ModelError::missing(sel, what))
+    }
+
+    // Whether the names count as duplicates of each other
+    pub fn same_name(&self, a: &str, b: &str) -> bool {
+        match self.case_insensitive_names {
+            true => a.to_lowercase() == b.to_lowercase(),
+            false => a == b,
         }
     }
 
-    pub fn get_target_mut(&mut self, sel: &Selection) -> Option<&mut Target> {
-        match sel {
-            Selection {
-                map: Some(msel),
-                zone: Some(zsel),
-                ability: Some(asel),
-                usage: Some(usel),
-            } => {
-                match (
-                    msel.get_selected(&self.maps),
-                    asel.get_selected(&self.abilities),
-                ) {
-                    (Some(map), Some(ability)) => {
-                        match (
-                            zsel.get_selected(&map.zones),
-                            usel.get_selected(&ability.usages),
-                        ) {
-                            (Some(zone), Some(usage)) => self.progress.get_mut(&(
-                                map.name.clone(),
-                                zone.name.clone(),
-                                ability.name.clone(),
-                                usage.name.clone(),
-                            )),
-                            _ => None,
-                        }
-                    }
-                    _ => None,
-                }
+    // Names must be valid and unique among their siblings, other than the one being renamed
+    fn check_unique<T>(
+        &self,
+        name: &str,
+        siblings: &[T],
+        what: &'static str,
+        renaming: Option<Id>,
+    ) -> Result<(), ModelError>
+    where
+        T: Nameable + Identifiable,
+    {
+        check_name(name)?;
+        if siblings
+            .iter()
+            .any(|v| self.same_name(v.name(), name) && Some(v.id()) != renaming)
+        {
+            return Err(ModelError::Duplicate(what, name.to_string()));
+        }
+        Ok(())
+    }
+}
+
+fn salvage_list<T, F>(v: Option<&Value>, what: &str, losses: &mut Vec<String>, salvage: F) -> Vec<T>
+where
+    F: Fn(&Value, &mut Vec<String>) -> Option<T>,
+{
+    let vs = match v.and_then(Value::as_array) {
+        Some(vs) => vs,
+        None => {
+            losses.push(format!("all {}", what));
+            return Vec::new();
+        }
+    };
+
+    let salvaged: Vec<T> = vs.iter().filter_map(|v| salvage(v, losses)).collect();
+    if salvaged.len() != vs.len() {
+        losses.push(format!(
+            "{} of {} {}",
+            vs.len() - salvaged.len(),
+            vs.len(),
+            what
+        ));
+    }
+    salvaged
+}
+
+// Haven with A Main and Garage, and Smoke with One-way and Default, each lineup with different
+// progress
+#[cfg(test)]
+pub fn test_store() -> ProgressStore {
+    let mut store = ProgressStore::new("Progress".into());
+    store.add_map("Haven".into()).unwrap();
+    for zone in ["A Main", "Garage"] {
+        store.add_zone(&Selector::Index(0), zone.into()).unwrap();
+    }
+    store.add_ability("Smoke".into()).unwrap();
+    for usage in ["One-way", "Default"] {
+        store.add_usage(&Selector::Index(0), usage.into()).unwrap();
+    }
+    for (i, target) in store.progress.values_mut().enumerate() {
+        target.progress = i as i32;
+    }
+    store
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn additions_report_why_they_failed() {
+        let mut store = test_store();
+        let haven = Selector::Name("Haven".into());
+        assert_eq!(
+            store.add_zone(&Selector::Name("Bind".into()), "Hookah".into()),
+            Err(ModelError::Unknown("map", "Bind".into()))
+        );
+        assert_eq!(
+            store.add_usage(&Selector::Index(3), "One-way".into()),
+            Err(ModelError::OutOfRange("ability", 3))
+        );
+        assert_eq!(
+            store.add_map("Haven".into()),
+            Err(ModelError::Duplicate("map", "Haven".into()))
+        );
+        assert_eq!(
+            store.add_zone(&haven, " ".into()),
+            Err(ModelError::InvalidName(" ".into(), "names cannot be blank"))
+        );
+        assert!(store.add_zone(&haven, "Hookah".into()).is_ok());
+    }
+
+    #[test]
+    fn names_may_be_unique_ignoring_case() {
+        let mut store = test_store();
+        assert!(store.add_map("haven".into()).is_ok());
+        store.case_insensitive_names = true;
+        assert_eq!(
+            store.add_map("HAVEN".into()),
+            Err(ModelError::Duplicate("map", "HAVEN".into()))
+        );
+        assert_eq!(
+            store.add_ability("42".into()),
+            Err(ModelError::InvalidName(
+                "42".into(),
+                "names cannot be numbers"
+            ))
+        );
+    }
+
+    #[test]
+    fn renames_and_removals_report_why_they_failed() {
+        let mut store = test_store();
+        store.add_map("Bind".into()).unwrap();
+        assert_eq!(
+            store.rename_map("Bind", "Haven".into()),
+            Err(ModelError::Duplicate("map", "Haven".into()))
+        );
+        assert!(store.rename_map("Bind", "Bind".into()).is_ok());
+        assert_eq!(
+            store.rm_usage("Smoke", "Recon"),
+            Err(ModelError::Unknown("usage", "Recon".into()))
+        );
+        assert_eq!(
+            store.move_ability("Flash", 1),
+            Err(ModelError::Unknown("ability", "Flash".into()))
+        );
+    }
+
+    #[test]
+    fn keys_name_what_is_not_selected() {
+        let mut store = test_store();
+        store.add_map("Bind".into()).unwrap();
+        let sel = Selection {
+            map: Some(Selector::Name("Bind".into())),
+            ..Selection::default()
+        };
+        assert_eq!(store.key(&sel), Err(ModelError::NoSelection("zone")));
+    }
+
+    #[test]
+    fn sorts_tolerate_negative_and_zero_targets() {
+        let mut store = test_store();
+        store.add_map("Bind".into()).unwrap();
+        store
+            .add_zone(&Selector::Index(1), "Hookah".into())
+            .unwrap();
+        let a_main = store_key(&store, 0);
+        let t = store.progress.get_mut(&a_main).unwrap();
+        t.change_progress(-2);
+        t.match_target_to_progress();
+        assert!(t.target < 0);
+
+        let bind = store.maps[1].id;
+        let hookah = *store.progress.keys().find(|k| k.0 == bind).unwrap();
+        store.progress.get_mut(&hookah).unwrap().target = 4;
+        store.sort_rows(SortKey::Completion, false);
+        store.sort_columns(SortKey::Completion, false);
+        assert_eq!(store.maps[0].name, "Bind");
+        assert_eq!(store.maps[1].name, "Haven");
+    }
+
+    #[test]
+    fn saves_are_repeatable() {
+        let save = || serde_json::to_string(&test_store()).unwrap();
+        assert_eq!(save(), save());
+    }
+
+    #[test]
+    fn merges_keep_both_sides_changes() {
+        fn with_progress(progress: i32) -> ProgressStore {
+            let mut store = test_store();
+            for target in store.progress.values_mut() {
+                target.progress = progress;
             }
-            _ => None,
+            store
         }
+        let base = with_progress(0);
+        let a_main = store_key(&base, 0);
+        let garage = store_key(&base, 1);
+
+        let mut theirs = with_progress(0);
+        theirs.progress.get_mut(&garage).unwrap().progress = 2;
+        theirs.progress.get_mut(&a_main).unwrap().progress = 3;
+        let mut ours = with_progress(0);
+        ours.progress.get_mut(&a_main).unwrap().progress = 1;
+        assert_eq!(ours.merge(&base, &theirs), Some(1));
+        assert_eq!(ours.progress[&a_main].progress, 1);
+        assert_eq!(ours.progress[&garage].progress, 2);
+
+        theirs.add_map("Bind".into()).unwrap();
+        let mut ours = with_progress(0);
+        ours.progress.get_mut(&a_main).unwrap().progress = 1;
+        assert_eq!(ours.merge(&base, &theirs), Some(1));
+        assert_eq!(ours.maps.len(), 2);
+
+        ours.add_ability("Flash".into()).unwrap();
+        assert_eq!(ours.merge(&base, &theirs), None);
+    }
+
+    fn store_key(store: &ProgressStore, zone: usize) -> LineupKey {
+        let sel = Selection {
+            zone: Some(Selector::Index(zone)),
+            ..Selection::default()
+        };
+        store.key(&sel).unwrap()
     }
 }
@@ -1,9 +1,11 @@
-use crate::model::{Nameable, ProgressStore, Target};
-use crate::selection::{Selection, Selector};
+use crate::model::{Ability, Id, LineupKey, Map, Nameable, ProgressStore, Target, Usage, Zone};
+use crate::selection::Selection;
 use tui::{
+    layout::{Constraint, Rect},
     style::{Color as Colour, Modifier, Style},
     widgets::{Block, Borders, Cell, Row, Table},
 };
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 pub trait Renderable<T, Selector = bool> {
     fn render(&self, selected: Selector) -> T;
@@ -21,14 +23,11 @@ where
 impl<'a> Renderable<Cell<'a>> for Target {
     fn render(&self, selected: bool) -> Cell<'a> {
         let mut style = Style::default();
-        let txt: String;
 
-        // Get text and styling
+        // Get styling
         if self.target == 0 {
-            txt = "-".into();
             style = style.fg(Colour::Blue);
         } else {
-            txt = format!("{}/{}", self.progress, self.target);
             style = style.fg(if self.progress.abs() <= self.target >> 2 {
                 Colour::Red
             } else if self.progress < self.target {
@@ -45,28 +44,266 @@ impl<'a> Renderable<Cell<'a>> for Target {
                 .add_modifier(Modifier::BOLD);
         }
 
-        Cell::from(txt).style(style)
+        Cell::from(target_text(self)).style(style)
     }
 }
 
-impl<'a> Renderable<(usize, Table<'a>), &Selection> for ProgressStore {
-    fn render(&self, selected: &Selection) -> (usize, Table<'a>) {
-        let abs_selected = selected.absolute(&self.maps, &self.abilities);
+fn target_text(t: &Target) -> String {
+    if t.target == 0 {
+        "-".into()
+    } else {
+        format!("{}/{}", t.progress, t.target)
+    }
+}
+
+// Columns are as wide as their contents, within these limits
+const MIN_WIDTH: u16 = 4;
+const MAX_WIDTH: u16 = 16;
+const MAX_NAME_WIDTH: u16 = 24;
+
+// How far the lineups are scrolled, in rows of zones and columns of usages
+#[derive(Default)]
+pub struct Viewport {
+    top: usize,
+    left: usize,
+    // Usage names are shortened to fit their lineups rather than widening the columns
+    pub abbreviate_usages: bool,
+    // The selected row and column last scrolled to, so that scrolling away from them sticks
+    followed: (Option<usize>, Option<usize>),
+    drawn: Drawn,
+}
+
+// Where the table was last drawn, to find what is clicked
+#[derive(Default)]
+struct Drawn {
+    x: u16,
+    y: u16,
+    width: u16,
+    height: u16,
+    name_widths: [u16; 2],
+    rows: Vec<(Id, Id)>,
+    cols: Vec<(Id, Id, u16)>,
+}
+
+#[derive(Clone, Copy)]
+pub enum Hit {
+    Lineup(LineupKey),
+    Zone(Id, Id),
+    Usage(Id, Id),
+}
+
+impl Viewport {
+    pub fn scroll_by(&mut self, rows: isize, cols: isize) {
+        self.top = self.top.saturating_add_signed(rows);
+        self.left = self.left.saturating_add_signed(cols);
+    }
+
+    pub fn hit(&self, x: u16, y: u16) -> Option<Hit> {
+        let drawn = &self.drawn;
+        let (Some(dx), Some(dy)) = (x.checked_sub(drawn.x), y.checked_sub(drawn.y)) else {
+            return None;
+        };
+        if dx >= drawn.width || dy >= drawn.height {
+            return None;
+        }
+        let row = (dy as usize)
+            .checked_sub(2)
+            .map(|r| drawn.rows.get(r).copied());
+
+        let mut right = drawn.name_widths[0] + drawn.name_widths[1] + 2;
+        if dx < right {
+            return match row {
+                Some(Some((m, z))) => Some(Hit::Zone(m, z)),
+                _ => None,
+            };
+        }
+        let (a, u, _) = drawn.cols.iter().copied().find(|(_, _, w)| {
+            right += w + 1;
+            dx < right
+        })?;
+        match row {
+            None => Some(Hit::Usage(a, u)),
+            Some(Some((m, z))) => Some(Hit::Lineup((m, z, a, u))),
+            Some(None) => None,
+        }
+    }
+}
+
+fn width(text: &str) -> u16 {
+    text.width().min(u16::MAX as usize) as u16
+}
+
+// Cuts the text down to the width, marking where it was cut
+fn fit(text: &str, width: u16) -> String {
+    let width = width as usize;
+    if text.width() <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let mut fitted = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        fitted.push(c);
+        used += w;
+    }
+    fitted.push('…');
+    fitted
+}
+
+// Drops vowels from inside words, then cuts what still does not fit
+fn abbreviate(name: &str, width: u16) -> String {
+    if name.width() <= width as usize {
+        return name.to_string();
+    }
+    let mut short = String::new();
+    let mut in_word = false;
+    for c in name.chars() {
+        if !in_word || !"aeiouAEIOU".contains(c) {
+            short.push(c);
+        }
+        in_word = c.is_alphanumeric();
+    }
+    fit(&short, width)
+}
+
+// Scrolls as little as possible to show the selected item
+fn reveal<F: Fn(usize) -> usize>(offset: &mut usize, selected: Option<usize>, fits: F) {
+    if let Some(s) = selected {
+        if s < *offset {
+            *offset = s;
+        }
+        while s >= *offset + fits(*offset) {
+            *offset += 1;
+        }
+    }
+}
+
+// Scrolls back to fill any space after the last item
+fn settle<F: Fn(usize) -> usize>(offset: &mut usize, total: usize, fits: F) {
+    while *offset > 0 && *offset - 1 + fits(*offset - 1) >= total {
+        *offset -= 1;
+    }
+}
+
+// The number of columns from the given one which fit in the space, at least one
+fn fitting(widths: &[u16], from: usize, space: u16) -> usize {
+    let mut used = 0;
+    let mut n = 0;
+    for w in widths.iter().skip(from) {
+        used += w + 1;
+        if used > space + 1 {
+            break;
+        }
+        n += 1;
+    }
+    n.max(1)
+}
+
+impl<'a> Renderable<(Vec<Constraint>, Table<'a>), (&Selection, &mut Viewport, Rect)>
+    for ProgressStore
+{
+    fn render(
+        &self,
+        (selection, viewport, area): (&Selection, &mut Viewport, Rect),
+    ) -> (Vec<Constraint>, Table<'a>) {
+        let selected_key = self.key(selection).ok();
+        let selected = self.selected(selection);
 
         let total_progress = self
             .progress
-            .iter()
-            .map(|(_, t)| t.progress)
+            .values()
+            .map(|t| t.progress)
             .reduce(|a, b| a + b)
             .unwrap_or(0);
         let total_target = self
             .progress
-            .iter()
-            .map(|(_, t)| t.target)
+            .values()
+            .map(|t| t.target)
             .reduce(|a, b| a + b)
             .unwrap_or(0);
         let progress_ratio = format!("{}/{}", total_progress, total_target);
-        let progress_pcge = format!("{}%", (total_progress as f32 / total_target as f32 * 100.0).floor());
+        let progress_pcge = format!(
+            "{}%",
+            (total_progress as f32 / total_target as f32 * 100.0).floor()
+        );
+
+        // Every zone is a row and every usage a column, the selected map or ability alone
+        // selects its first
+        let rows: Vec<(&Map, &Zone)> = self
+            .maps
+            .iter()
+            .flat_map(|m| m.zones.iter().map(move |z| (m, z)))
+            .collect();
+        let cols: Vec<(&Ability, &Usage)> = self
+            .abilities
+            .iter()
+            .flat_map(|a| a.usages.iter().map(move |u| (a, u)))
+            .collect();
+        let selected_row = selected.map.and_then(|sm| {
+            rows.iter()
+                .position(|(m, z)| m.id == sm.id && selected.zone.is_none_or(|sz| sz.id == z.id))
+        });
+        let selected_col = selected.ability.and_then(|sa| {
+            cols.iter()
+                .position(|(a, u)| a.id == sa.id && selected.usage.is_none_or(|su| su.id == u.id))
+        });
+
+        let name_widths = [
+            rows.iter()
+                .map(|(m, _)| width(&m.name))
+                .fold(width("Total"), u16::max),
+            rows.iter()
+                .map(|(_, z)| width(&z.name))
+                .fold(width(&progress_ratio).max(width(&progress_pcge)), u16::max),
+        ]
+        .map(|w| w.clamp(MIN_WIDTH, MAX_NAME_WIDTH));
+        let widths: Vec<u16> = cols
+            .iter()
+            .map(|(a, u)| {
+                let header = match (viewport.abbreviate_usages, a.usages[0].id == u.id) {
+                    (true, _) => 0,
+                    (false, true) => width(&u.name).max(width(&a.name)),
+                    (false, false) => width(&u.name),
+                };
+                rows.iter()
+                    .filter_map(|(m, z)| self.progress.get(&(m.id, z.id, a.id, u.id)))
+                    .map(|t| width(&target_text(t)))
+                    .fold(header, u16::max)
+                    .clamp(MIN_WIDTH, MAX_WIDTH)
+            })
+            .collect();
+
+        // The two header rows and two name columns stay put
+        let height = area.height.saturating_sub(2 + 2).max(1) as usize;
+        let space = area
+            .width
+            .saturating_sub(2 + name_widths[0] + name_widths[1] + 2);
+        let fits = |from| fitting(&widths, from, space);
+        if viewport.followed != (selected_row, selected_col) {
+            reveal(&mut viewport.top, selected_row, |_| height);
+            reveal(&mut viewport.left, selected_col, fits);
+            viewport.followed = (selected_row, selected_col);
+        }
+        settle(&mut viewport.top, rows.len(), |_| height);
+        settle(&mut viewport.left, cols.len(), fits);
+        let shown_rows: Vec<_> = rows
+            .iter()
+            .enumerate()
+            .skip(viewport.top)
+            .take(height)
+            .collect();
+        let shown_cols: Vec<_> = cols
+            .iter()
+            .enumerate()
+            .skip(viewport.left)
+            .take(fitting(&widths, viewport.left, space))
+            .collect();
 
         let mut primary_hdr: Vec<Cell> = vec![
             Cell::from("Total").style(Style::default().fg(Colour::Blue)),
@@ -76,60 +313,303 @@ impl<'a> Renderable<(usize, Table<'a>), &Selection> for ProgressStore {
             Cell::from(""),
             Cell::from(progress_pcge).style(Style::default().fg(Colour::Blue)),
         ];
+        let mut constraints = name_widths.map(Constraint::Length).to_vec();
+        for (i, (a, u)) in &shown_cols {
+            let first = *i == viewport.left || a.usages[0].id == u.id;
+            let ability = if first {
+                fit(&a.name, widths[*i])
+            } else {
+                "".into()
+            };
+            let usage = match viewport.abbreviate_usages {
+                true => abbreviate(&u.name, widths[*i]),
+                false => fit(&u.name, widths[*i]),
+            };
+            primary_hdr.push(Cell::from(ability));
+            secondary_hdr.push(Cell::from(usage));
+            constraints.push(Constraint::Length(widths[*i]));
+        }
+
+        let err_style = Style::default().fg(Colour::Red);
 
-        for a in &self.abilities {
-            let mut pushed_this_ability_name = false;
-            for u in &a.usages {
-                primary_hdr.push(Cell::from(if pushed_this_ability_name {
-                    "".to_string()
-                } else {
-                    pushed_this_ability_name = true;
-                    a.name.clone()
-                }));
-                secondary_hdr.push(Cell::from(u.name.clone()));
+        let mut table_rows = vec![Row::new(primary_hdr), Row::new(secondary_hdr)];
+        for (i, (m, z)) in &shown_rows {
+            let first = *i == viewport.top || m.zones[0].id == z.id;
+            let map = if first {
+                fit(&m.name, name_widths[0])
+            } else {
+                "".into()
+            };
+            let mut row = vec![Cell::from(map), Cell::from(fit(&z.name, name_widths[1]))];
+            for (_, (a, u)) in &shown_cols {
+                let key = (m.id, z.id, a.id, u.id);
+                row.push(match self.progress.get(&key) {
+                    Some(t) => t.render(selected_key == Some(key)),
+                    None => Cell::from("??".to_string()).style(err_style),
+                });
             }
+            table_rows.push(Row::new(row));
         }
 
-        let err_style = Style::default().fg(Colour::Red);
+        // Counts of what is scrolled out of sight on each side
+        let hidden = [
+            ("↑", viewport.top),
+            ("↓", rows.len().saturating_sub(viewport.top + height)),
+            ("←", viewport.left),
+            (
+                "→",
+                cols.len().saturating_sub(viewport.left + shown_cols.len()),
+            ),
+        ];
+        viewport.drawn = Drawn {
+            x: area.x + 1,
+            y: area.y + 1,
+            width: area.width.saturating_sub(2),
+            height: area.height.saturating_sub(2),
+            name_widths,
+            rows: shown_rows.iter().map(|(_, (m, z))| (m.id, z.id)).collect(),
+            cols: shown_cols
+                .iter()
+                .map(|(i, (a, u))| (a.id, u.id, widths[*i]))
+                .collect(),
+        };
 
-        let ncols = primary_hdr.len();
-        let mut rows = vec![Row::new(primary_hdr), Row::new(secondary_hdr)];
-        for (mi, m) in self.maps.iter().enumerate() {
-            let mut pushed_this_map_name = false;
-            for (zi, z) in m.zones.iter().enumerate() {
-                let mut row = Vec::new();
-                row.push(Cell::from(if pushed_this_map_name {
-                    "".to_string()
-                } else {
-                    pushed_this_map_name = true;
-                    m.name.clone()
-                }));
-                row.push(Cell::from(z.name.clone()));
-                for (ai, a) in self.abilities.iter().enumerate() {
-                    for (ui, _) in a.usages.iter().enumerate() {
-                        let sel = Selection {
-                            map: Some(Selector::Index(mi)),
-                            zone: Some(Selector::Index(zi)),
-                            ability: Some(Selector::Index(ai)),
-                            usage: Some(Selector::Index(ui)),
-                        };
-
-                        row.push(match self.get_target(&sel) {
-                            Some(t) => t.render(sel == abs_selected),
-                            None => Cell::from("??".to_string()).style(err_style),
-                        });
-                    }
-                }
-                rows.push(Row::new(row));
+        let mut title = self.name.clone();
+        for (arrow, n) in hidden {
+            if n > 0 {
+                title.push_str(&format!(" {}{}", arrow, n));
             }
         }
         (
-            ncols,
-            Table::new(rows).block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(self.name.clone()),
-            ),
+            constraints,
+            Table::new(table_rows).block(Block::default().borders(Borders::ALL).title(title)),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::Selector;
+
+    // A map of zones by an ability of usages, every column as narrow as allowed
+    fn grid(zones: usize, usages: usize) -> ProgressStore {
+        let mut store = ProgressStore::new("Progress".into());
+        store.add_map("M".into()).unwrap();
+        for z in 0..zones {
+            store
+                .add_zone(&Selector::Index(0), format!("Z{}", z))
+                .unwrap();
+        }
+        store.add_ability("A".into()).unwrap();
+        for u in 0..usages {
+            store
+                .add_usage(&Selector::Index(0), format!("U{}", u))
+                .unwrap();
+        }
+        store
+    }
+
+    // Room for three rows and three columns
+    const AREA: Rect = Rect {
+        x: 0,
+        y: 0,
+        width: 2 + 5 + 5 + 2 + 3 * 5 - 1,
+        height: 2 + 2 + 3,
+    };
+
+    fn draw(store: &ProgressStore, selection: &Selection, viewport: &mut Viewport) {
+        store.render((selection, viewport, AREA));
+    }
+
+    fn lineup(zone: usize, usage: usize) -> Selection {
+        Selection {
+            map: Some(Selector::Index(0)),
+            zone: Some(Selector::Index(zone)),
+            ability: Some(Selector::Index(0)),
+            usage: Some(Selector::Index(usage)),
+        }
+    }
+
+    #[test]
+    fn selections_are_scrolled_to() {
+        let store = grid(10, 10);
+        let mut viewport = Viewport::default();
+        draw(&store, &lineup(9, 9), &mut viewport);
+        assert_eq!((viewport.top, viewport.left), (7, 7));
+        draw(&store, &lineup(8, 2), &mut viewport);
+        assert_eq!((viewport.top, viewport.left), (7, 2));
+        draw(&store, &lineup(0, 4), &mut viewport);
+        assert_eq!((viewport.top, viewport.left), (0, 2));
+
+        // Scrolling away from the selection sticks until another is made
+        viewport.scroll_by(5, 5);
+        draw(&store, &lineup(0, 4), &mut viewport);
+        assert_eq!((viewport.top, viewport.left), (5, 7));
+        draw(&store, &lineup(1, 5), &mut viewport);
+        assert_eq!((viewport.top, viewport.left), (1, 5));
+    }
+
+    #[test]
+    fn scrolling_stops_at_the_edges() {
+        let store = grid(10, 10);
+        let mut viewport = Viewport::default();
+        viewport.scroll_by(-1, -100);
+        draw(&store, &Selection::new(), &mut viewport);
+        assert_eq!((viewport.top, viewport.left), (0, 0));
+
+        viewport.scroll_by(100, 8);
+        draw(&store, &Selection::new(), &mut viewport);
+        assert_eq!((viewport.top, viewport.left), (7, 7));
+
+        viewport.scroll_by(isize::MIN, isize::MIN);
+        draw(&store, &Selection::new(), &mut viewport);
+        assert_eq!((viewport.top, viewport.left), (0, 0));
+    }
+
+    #[test]
+    fn shrinking_tables_scroll_back() {
+        let mut store = grid(10, 10);
+        let mut viewport = Viewport::default();
+        viewport.scroll_by(7, 7);
+        draw(&store, &Selection::new(), &mut viewport);
+        assert_eq!((viewport.top, viewport.left), (7, 7));
+
+        for i in 5..10 {
+            store.rm_zone("M", &format!("Z{}", i)).unwrap();
+            store.rm_usage("A", &format!("U{}", i)).unwrap();
+        }
+        draw(&store, &Selection::new(), &mut viewport);
+        assert_eq!((viewport.top, viewport.left), (2, 2));
+
+        for i in 1..5 {
+            store.rm_zone("M", &format!("Z{}", i)).unwrap();
+            store.rm_usage("A", &format!("U{}", i)).unwrap();
+        }
+        draw(&store, &Selection::new(), &mut viewport);
+        assert_eq!((viewport.top, viewport.left), (0, 0));
+    }
+
+    #[test]
+    fn reveals_scroll_as_little_as_possible() {
+        let mut offset = 5;
+        reveal(&mut offset, None, |_| 3);
+        assert_eq!(offset, 5);
+        reveal(&mut offset, Some(6), |_| 3);
+        assert_eq!(offset, 5);
+        reveal(&mut offset, Some(2), |_| 3);
+        assert_eq!(offset, 2);
+        reveal(&mut offset, Some(10), |_| 3);
+        assert_eq!(offset, 8);
+        // What fits may depend on where it starts
+        reveal(&mut offset, Some(12), |from| if from < 10 { 1 } else { 3 });
+        assert_eq!(offset, 10);
+
+        settle(&mut offset, 11, |_| 3);
+        assert_eq!(offset, 8);
+        settle(&mut offset, 20, |_| 3);
+        assert_eq!(offset, 8);
+        settle(&mut offset, 0, |_| 3);
+        assert_eq!(offset, 0);
+    }
+
+    #[test]
+    fn text_is_cut_to_fit() {
+        assert_eq!(fit("Haven", 5), "Haven");
+        assert_eq!(fit("Haven", 4), "Hav…");
+        assert_eq!(fit("Haven", 1), "…");
+        assert_eq!(fit("Haven", 0), "");
+        assert_eq!(fit("", 0), "");
+        // Wide characters are not split, even if that leaves a column spare
+        assert_eq!(fit("日本語", 6), "日本語");
+        assert_eq!(fit("日本語", 5), "日本…");
+        assert_eq!(fit("日本語", 4), "日…");
+        assert_eq!(fit("日本語", 2), "…");
+        assert_eq!(fit("a日", 2), "a…");
+    }
+
+    #[test]
+    fn names_are_abbreviated_to_fit() {
+        assert_eq!(abbreviate("Default", 7), "Default");
+        assert_eq!(abbreviate("Default", 6), "Dflt");
+        assert_eq!(abbreviate("One-way", 5), "On-wy");
+        assert_eq!(abbreviate("One-way", 4), "On-…");
+        assert_eq!(abbreviate("Ascent", 1), "…");
+        assert_eq!(abbreviate("Ascent", 0), "");
+        // Names narrower than the ellipsis are never abbreviated
+        assert_eq!(abbreviate("A", 1), "A");
+        assert_eq!(abbreviate("", 0), "");
+        assert_eq!(abbreviate("Übergang", 5), "Übrg…");
+        assert_eq!(abbreviate("日本語", 3), "日…");
+    }
+
+    #[test]
+    fn at_least_one_column_fits() {
+        assert_eq!(fitting(&[4, 4, 4], 0, 14), 3);
+        assert_eq!(fitting(&[4, 4, 4], 0, 13), 2);
+        assert_eq!(fitting(&[4, 4, 4], 1, 14), 2);
+        assert_eq!(fitting(&[4, 4, 4], 0, 0), 1);
+        assert_eq!(fitting(&[16], 0, 4), 1);
+        assert_eq!(fitting(&[4, 4], 5, 14), 1);
+        assert_eq!(fitting(&[], 0, 14), 1);
+    }
+
+    #[test]
+    fn clicks_hit_what_is_drawn_there() {
+        let store = grid(10, 10);
+        let zone = |i: usize| (store.maps[0].id, store.maps[0].zones[i].id);
+        let usage = |i: usize| (store.abilities[0].id, store.abilities[0].usages[i].id);
+        let mut viewport = Viewport::default();
+        let hit = |viewport: &Viewport, x, y| match viewport.hit(x, y) {
+            Some(Hit::Lineup((m, z, a, u))) => format!("lineup {:?}", ((m, z), (a, u))),
+            Some(Hit::Zone(m, z)) => format!("zone {:?}", (m, z)),
+            Some(Hit::Usage(a, u)) => format!("usage {:?}", (a, u)),
+            None => "nothing".into(),
+        };
+
+        // Nothing has been drawn yet
+        assert_eq!(hit(&viewport, 13, 3), "nothing");
+
+        draw(&store, &Selection::new(), &mut viewport);
+        assert_eq!(
+            hit(&viewport, 13, 3),
+            format!("lineup {:?}", (zone(0), usage(0)))
+        );
+        assert_eq!(
+            hit(&viewport, 22, 5),
+            format!("lineup {:?}", (zone(2), usage(1)))
+        );
+        assert_eq!(hit(&viewport, 1, 3), format!("zone {:?}", zone(0)));
+        assert_eq!(hit(&viewport, 12, 4), format!("zone {:?}", zone(1)));
+        assert_eq!(hit(&viewport, 13, 1), format!("usage {:?}", usage(0)));
+        assert_eq!(hit(&viewport, 26, 2), format!("usage {:?}", usage(2)));
+
+        // The headers over the names, the borders and anything past the table hit nothing
+        assert_eq!(hit(&viewport, 1, 1), "nothing");
+        assert_eq!(hit(&viewport, 5, 2), "nothing");
+        assert_eq!(hit(&viewport, 0, 3), "nothing");
+        assert_eq!(hit(&viewport, 13, 0), "nothing");
+        assert_eq!(hit(&viewport, 27, 3), "nothing");
+        assert_eq!(hit(&viewport, 1, 6), "nothing");
+        assert_eq!(hit(&viewport, 13, 6), "nothing");
+        assert_eq!(hit(&viewport, u16::MAX, u16::MAX), "nothing");
+
+        // The names and headers stay put as the lineups scroll beneath them
+        viewport.scroll_by(7, 7);
+        draw(&store, &Selection::new(), &mut viewport);
+        assert_eq!(
+            hit(&viewport, 13, 3),
+            format!("lineup {:?}", (zone(7), usage(7)))
+        );
+        assert_eq!(hit(&viewport, 1, 5), format!("zone {:?}", zone(9)));
+        assert_eq!(hit(&viewport, 18, 2), format!("usage {:?}", usage(8)));
+
+        // Short tables leave space below them
+        let store = grid(1, 1);
+        let usage = (store.abilities[0].id, store.abilities[0].usages[0].id);
+        draw(&store, &Selection::new(), &mut viewport);
+        assert_eq!(hit(&viewport, 13, 2), format!("usage {:?}", usage));
+        assert_eq!(hit(&viewport, 13, 4), "nothing");
+        assert_eq!(hit(&viewport, 18, 3), "nothing");
+    }
+}
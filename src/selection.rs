@@ -19,33 +19,7 @@ impl Selection {
         }
     }
 
-    pub fn absolute(&self, maps: &Vec<Map>, abilities: &Vec<Ability>) -> Self {
-        let mut nmap = None;
-        let mut nzone = None;
-        let mut nability = None;
-        let mut nusage = None;
-        if let Some(map) = &self.map {
-            if let (Some(zone), Some(m)) = (&self.zone, map.get_selected(maps)) {
-                nzone = zone.to_index(&m.zones);
-            }
-            nmap = map.to_index(maps);
-        }
-
-        if let Some(ability) = &self.ability {
-            if let (Some(usage), Some(a)) = (&self.usage, ability.get_selected(abilities)) {
-                nusage = usage.to_index(&a.usages);
-            }
-            nability = ability.to_index(abilities);
-        }
-        Self {
-            map: nmap,
-            zone: nzone,
-            ability: nability,
-            usage: nusage,
-        }
-    }
-
-    pub fn relative(&self, maps: &Vec<Map>, abilities: &Vec<Ability>) -> Self {
+    pub fn relative(&self, maps: &[Map], abilities: &[Ability]) -> Self {
         let mut nmap = None;
         let mut nzone = None;
         let mut nability = None;
@@ -71,7 +45,7 @@ impl Selection {
         }
     }
 
-    pub fn next_zone(&mut self, maps: &Vec<Map>) {
+    pub fn next_zone(&mut self, maps: &[Map]) {
         if let Some(map) = &self.map {
             self.map = map.to_index(maps);
             if let Some(Some(m)) = self.map.as_ref().map(|m| m.get_selected(maps)) {
@@ -81,19 +55,11 @@ impl Selection {
             }
         }
 
-        if let (Some(msel), Some(Selector::Index(zidx))) = (&self.map, &self.zone) {
-            if let Some(m) = msel.get_selected(maps) {
-                if *zidx < m.zones.len() - 1 {
-                    self.zone = Some(Selector::Index(zidx + 1));
-                } else if let Selector::Index(midx) = msel {
-                    self.map = Some(Selector::Index((midx + 1) % maps.len()));
-                    self.zone = Some(Selector::Index(0));
-                }
-            }
-        }
+        let lens: Vec<_> = maps.iter().map(|m| m.zones.len()).collect();
+        step(&mut self.map, &mut self.zone, &lens, true);
     }
 
-    pub fn prev_zone(&mut self, maps: &Vec<Map>) {
+    pub fn prev_zone(&mut self, maps: &[Map]) {
         if let Some(map) = &self.map {
             self.map = map.to_index(maps);
             if let Some(Some(m)) = self.map.as_ref().map(|m| m.get_selected(maps)) {
@@ -103,24 +69,11 @@ impl Selection {
             }
         }
 
-        if let (Some(msel), Some(Selector::Index(zidx))) = (&self.map, &self.zone) {
-            if *zidx == 0 {
-                if let Selector::Index(midx) = msel {
-                    let new_msel = if *midx == 0 {
-                        Selector::Index(maps.len() - 1)
-                    } else {
-                        Selector::Index(midx - 1)
-                    };
-                    self.zone = Some(Selector::Index(new_msel.get_selected(maps).unwrap().zones.len() - 1));
-                    self.map = Some(new_msel);
-                }
-            } else {
-                self.zone = Some(Selector::Index(zidx - 1));
-            }
-        }
+        let lens: Vec<_> = maps.iter().map(|m| m.zones.len()).collect();
+        step(&mut self.map, &mut self.zone, &lens, false);
     }
 
-    pub fn next_usage(&mut self, abilities: &Vec<Ability>) {
+    pub fn next_usage(&mut self, abilities: &[Ability]) {
         if let Some(ability) = &self.ability {
             self.ability = ability.to_index(abilities);
             if let Some(Some(a)) = self.ability.as_ref().map(|m| m.get_selected(abilities)) {
@@ -130,19 +83,11 @@ impl Selection {
             }
         }
 
-        if let (Some(asel), Some(Selector::Index(uidx))) = (&self.ability, &self.usage) {
-            if let Some(a) = asel.get_selected(abilities) {
-                if *uidx < a.usages.len() - 1 {
-                    self.usage = Some(Selector::Index(uidx + 1));
-                } else if let Selector::Index(aidx) = asel {
-                    self.ability = Some(Selector::Index((aidx + 1) % abilities.len()));
-                    self.usage = Some(Selector::Index(0));
-                }
-            }
-        }
+        let lens: Vec<_> = abilities.iter().map(|a| a.usages.len()).collect();
+        step(&mut self.ability, &mut self.usage, &lens, true);
     }
 
-    pub fn prev_usage(&mut self, abilities: &Vec<Ability>) {
+    pub fn prev_usage(&mut self, abilities: &[Ability]) {
         if let Some(ability) = &self.ability {
             self.ability = ability.to_index(abilities);
             if let Some(Some(a)) = self.ability.as_ref().map(|m| m.get_selected(abilities)) {
@@ -152,24 +97,35 @@ impl Selection {
             }
         }
 
-        if let (Some(asel), Some(Selector::Index(uidx))) = (&self.ability, &self.usage) {
-            if *uidx == 0 {
-                if let Selector::Index(aidx) = asel {
-                    let new_asel = if *aidx == 0 {
-                        Selector::Index(abilities.len() - 1)
-                    } else {
-                        Selector::Index(aidx - 1)
-                    };
-                    self.usage = Some(Selector::Index(new_asel.get_selected(abilities).unwrap().usages.len() - 1));
-                    self.ability = Some(new_asel);
-                }
-            } else {
-                self.usage = Some(Selector::Index(uidx - 1));
-            }
-        }
+        let lens: Vec<_> = abilities.iter().map(|a| a.usages.len()).collect();
+        step(&mut self.ability, &mut self.usage, &lens, false);
     }
 }
 
+// Steps an inner selector through every inner item in turn, wrapping between outer items and
+// skipping those which contain nothing.
+fn step(outer: &mut Option<Selector>, inner: &mut Option<Selector>, lens: &[usize], forward: bool) {
+    let (Some(Selector::Index(o)), Some(Selector::Index(i))) = (&outer, &inner) else {
+        return;
+    };
+    let positions: Vec<_> = lens
+        .iter()
+        .enumerate()
+        .flat_map(|(o, len)| (0..*len).map(move |i| (o, i)))
+        .collect();
+    let Some(pos) = positions.iter().position(|p| *p == (*o, *i)) else {
+        return;
+    };
+    let n = positions.len();
+    let (o, i) = positions[if forward {
+        (pos + 1) % n
+    } else {
+        (pos + n - 1) % n
+    }];
+    *outer = Some(Selector::Index(o));
+    *inner = Some(Selector::Index(i));
+}
+
 impl Default for Selection {
     fn default() -> Self {
         Self {
@@ -187,8 +143,45 @@ pub enum Selector {
     Name(String),
 }
 
+#[derive(Eq, PartialEq, Debug)]
+pub enum Resolved {
+    Found(usize),
+    Ambiguous(Vec<usize>),
+    Missing,
+}
+
 impl Selector {
-    pub fn get_selected<'a, S>(&self, vs: &'a Vec<S>) -> Option<&'a S>
+    pub fn resolve<S>(&self, vs: &[S]) -> Resolved
+    where
+        S: Nameable,
+    {
+        self.resolve_with(vs, resolve_name)
+    }
+
+    pub fn resolve_strictly<S>(&self, vs: &[S]) -> Resolved
+    where
+        S: Nameable,
+    {
+        self.resolve_with(vs, resolve_name_strictly)
+    }
+
+    fn resolve_with<S>(&self, vs: &[S], resolve: fn(&str, &[&str]) -> Resolved) -> Resolved
+    where
+        S: Nameable,
+    {
+        match self {
+            Selector::Name(query) => {
+                let names: Vec<_> = vs.iter().map(|v| v.name().as_str()).collect();
+                resolve(query, &names)
+            }
+            Selector::Index(_) => match self.get_selected_idx(vs) {
+                Some(i) => Resolved::Found(i),
+                None => Resolved::Missing,
+            },
+        }
+    }
+
+    pub fn get_selected<'a, S>(&self, vs: &'a [S]) -> Option<&'a S>
     where
         S: Nameable,
         // T: SliceIndex<usize, Output=S> + IntoIterator<Item = S>,
@@ -196,7 +189,7 @@ impl Selector {
         self.get_selected_idx(vs).map(|i| &vs[i])
     }
 
-    pub fn get_selected_mut<'a, S>(&self, vs: &'a mut Vec<S>) -> Option<&'a mut S>
+    pub fn get_selected_mut<'a, S>(&self, vs: &'a mut [S]) -> Option<&'a mut S>
     where
         S: Nameable,
         // T: SliceIndex<usize, Output=S> + IntoIterator<Item = S>,
@@ -204,7 +197,7 @@ impl Selector {
         self.get_selected_idx(vs).map(|i| &mut vs[i])
     }
 
-    fn get_selected_idx<S>(&self, vs: &Vec<S>) -> Option<usize>
+    fn get_selected_idx<S>(&self, vs: &[S]) -> Option<usize>
     where
         S: Nameable,
         // T: SliceIndex<usize, Output=S> + IntoIterator<Item = S>,
@@ -228,15 +221,23 @@ impl Selector {
         }
     }
 
-    pub fn to_index<S>(&self, vs: &Vec<S>) -> Option<Selector>
+    pub fn rename(&mut self, from: &str, to: &str) {
+        if let Selector::Name(name) = self {
+            if name == from {
+                *name = to.to_string();
+            }
+        }
+    }
+
+    pub fn to_index<S>(&self, vs: &[S]) -> Option<Selector>
     where
         S: Nameable,
         // T: SliceIndex<usize, Output=S> + IntoIterator<Item = S>,
     {
-        self.get_selected_idx(vs).map(|i| Selector::Index(i))
+        self.get_selected_idx(vs).map(Selector::Index)
     }
 
-    pub fn to_name<S>(&self, vs: &Vec<S>) -> Option<Selector>
+    pub fn to_name<S>(&self, vs: &[S]) -> Option<Selector>
     where
         S: Nameable,
         // T: SliceIndex<usize, Output=S> + IntoIterator<Item = S>,
@@ -260,3 +261,195 @@ impl From<String> for Selector {
         }
     }
 }
+
+// Tiers of match_tier, from best to worst
+const PREFIX: u8 = 1;
+const LOOSE: u8 = 3;
+
+pub fn resolve_name(query: &str, names: &[&str]) -> Resolved {
+    resolve_within(query, names, LOOSE)
+}
+
+// Removals and renames are hard to take back, so only the whole name or a prefix of it will do
+pub fn resolve_name_strictly(query: &str, names: &[&str]) -> Resolved {
+    resolve_within(query, names, PREFIX)
+}
+
+fn resolve_within(query: &str, names: &[&str], worst: u8) -> Resolved {
+    // Nothing typed matches every name, which only makes sense when completing
+    if query.trim().is_empty() {
+        return Resolved::Missing;
+    }
+    if let Some(i) = names.iter().position(|n| *n == query) {
+        return Resolved::Found(i);
+    }
+    match best_matches(query, names, worst).as_slice() {
+        [] => Resolved::Missing,
+        [i] => Resolved::Found(*i),
+        is => Resolved::Ambiguous(is.to_vec()),
+    }
+}
+
+// Returns the indices of the names which best match the query, in their original order.
+// Exact matches beat prefixes, which beat word abbreviations ("a m" or "am" for "A Main"),
+// which beat any other names containing the query's characters in order.
+pub fn matches(query: &str, names: &[&str]) -> Vec<usize> {
+    best_matches(query, names, LOOSE)
+}
+
+fn best_matches(query: &str, names: &[&str], worst: u8) -> Vec<usize> {
+    let query = query.trim().to_lowercase();
+    let tiers: Vec<_> = names
+        .iter()
+        .map(|n| match_tier(&query, &n.to_lowercase()).filter(|t| *t <= worst))
+        .collect();
+    let Some(best) = tiers.iter().flatten().min() else {
+        return Vec::new();
+    };
+    tiers
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| t.as_ref() == Some(best))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn match_tier(query: &str, name: &str) -> Option<u8> {
+    if name == query {
+        return Some(0);
+    }
+    if name.starts_with(query) {
+        return Some(1);
+    }
+
+    let mut words = name.split_whitespace();
+    let abbreviates = query
+        .split_whitespace()
+        .all(|q| words.any(|w| w.starts_with(q)));
+    let initials: String = name
+        .split_whitespace()
+        .filter_map(|w| w.chars().next())
+        .collect();
+    if abbreviates || initials.starts_with(query) {
+        return Some(2);
+    }
+
+    let mut chars = name.chars();
+    if query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .all(|q| chars.any(|c| c == q))
+    {
+        return Some(3);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{test_store, ProgressStore};
+
+    const NAMES: [&str; 5] = ["A Main", "A Site", "Garage", "Main", "Main Hall"];
+
+    #[test]
+    fn names_resolve_by_best_match() {
+        assert_eq!(match_tier("main", "main"), Some(0));
+        assert_eq!(match_tier("gar", "garage"), Some(1));
+        assert_eq!(match_tier("m h", "main hall"), Some(2));
+        assert_eq!(match_tier("am", "a main"), Some(2));
+        assert_eq!(match_tier("grg", "garage"), Some(3));
+        assert_eq!(match_tier("x", "garage"), None);
+
+        assert_eq!(matches("main", &NAMES), [3]);
+        assert_eq!(matches("MAIN H", &NAMES), [4]);
+        assert_eq!(resolve_name("Main", &NAMES), Resolved::Found(3));
+        assert_eq!(resolve_name("gar", &NAMES), Resolved::Found(2));
+        assert_eq!(resolve_name("a m", &NAMES), Resolved::Found(0));
+        assert_eq!(resolve_name("mh", &NAMES), Resolved::Found(4));
+        assert_eq!(resolve_name("a", &NAMES), Resolved::Ambiguous(vec![0, 1]));
+        assert_eq!(resolve_name("xyz", &NAMES), Resolved::Missing);
+    }
+
+    #[test]
+    fn strict_names_must_be_whole_or_prefixes() {
+        assert_eq!(resolve_name_strictly("gar", &NAMES), Resolved::Found(2));
+        assert_eq!(resolve_name_strictly("MAIN", &NAMES), Resolved::Found(3));
+        assert_eq!(
+            resolve_name_strictly("a", &NAMES),
+            Resolved::Ambiguous(vec![0, 1])
+        );
+        assert_eq!(resolve_name_strictly("mh", &NAMES), Resolved::Missing);
+        assert_eq!(resolve_name_strictly("grg", &NAMES), Resolved::Missing);
+        assert_eq!(resolve_name_strictly("", &NAMES), Resolved::Missing);
+    }
+
+    #[test]
+    fn empty_queries_resolve_to_nothing() {
+        assert_eq!(resolve_name("", &NAMES), Resolved::Missing);
+        assert_eq!(resolve_name("  ", &NAMES), Resolved::Missing);
+        // Though completing them offers everything
+        assert_eq!(matches("", &NAMES), [0, 1, 2, 3, 4]);
+    }
+
+    fn zone_at(map: usize, zone: usize) -> Selection {
+        Selection {
+            map: Some(Selector::Index(map)),
+            zone: Some(Selector::Index(zone)),
+            ..Selection::default()
+        }
+    }
+
+    #[test]
+    fn steps_wrap_and_skip_empty_items() {
+        let mut store = test_store();
+        store.add_map("Bind".into()).unwrap();
+        store.add_map("Split".into()).unwrap();
+        store
+            .add_zone(&Selector::Index(2), "B Main".into())
+            .unwrap();
+
+        let mut sel = Selection::default();
+        for expected in [zone_at(0, 1), zone_at(2, 0), zone_at(0, 0)] {
+            sel.next_zone(&store.maps);
+            assert_eq!(sel, expected);
+        }
+        sel.prev_zone(&store.maps);
+        assert_eq!(sel, zone_at(2, 0));
+
+        sel.next_usage(&store.abilities);
+        sel.prev_usage(&store.abilities);
+        assert_eq!(sel, zone_at(2, 0));
+    }
+
+    #[test]
+    fn steps_through_one_or_no_items_stay_put() {
+        let mut store = ProgressStore::new("Progress".into());
+        let mut sel = Selection::default();
+        sel.next_zone(&store.maps);
+        sel.prev_usage(&store.abilities);
+        assert_eq!(sel.map, None);
+        assert_eq!(sel.ability, None);
+
+        // Nothing to step through, so nothing is selected
+        store.add_map("Haven".into()).unwrap();
+        store.add_ability("Smoke".into()).unwrap();
+        let mut sel = Selection::default();
+        sel.next_zone(&store.maps);
+        sel.prev_usage(&store.abilities);
+        assert_eq!((sel.zone, sel.usage), (None, None));
+
+        store
+            .add_zone(&Selector::Index(0), "Garage".into())
+            .unwrap();
+        store
+            .add_usage(&Selector::Index(0), "One-way".into())
+            .unwrap();
+        let mut sel = Selection::default();
+        sel.next_zone(&store.maps);
+        sel.prev_zone(&store.maps);
+        sel.next_usage(&store.abilities);
+        sel.prev_usage(&store.abilities);
+        assert_eq!(sel, Selection::default());
+    }
+}
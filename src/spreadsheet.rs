@@ -0,0 +1,345 @@
+use crate::model::{check_name, LineupKey, Nameable, ProgressStore};
+use crate::selection::Selector;
+use csv::{ReaderBuilder, StringRecord, Trim, Writer};
+use std::fmt::Display;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+const COLUMNS: [&str; 6] = ["map", "zone", "ability", "usage", "progress", "target"];
+
+// A lineup read from either layout, with its progress and target if these were given
+struct Row {
+    line: u64,
+    names: [String; 4],
+    target: Option<(i32, i32)>,
+}
+
+// One row per lineup, leaving the progress and target of untracked lineups empty
+pub fn export_long<W: Write>(store: &ProgressStore, w: W) -> Result<()> {
+    let mut out = Writer::from_writer(w);
+    out.write_record(COLUMNS)?;
+    for m in &store.maps {
+        for z in &m.zones {
+            for a in &store.abilities {
+                for u in &a.usages {
+                    let (progress, target) = match store.progress.get(&(m.id, z.id, a.id, u.id)) {
+                        Some(t) => (t.progress.to_string(), t.target.to_string()),
+                        None => (String::new(), String::new()),
+                    };
+                    out.write_record([&m.name, &z.name, &a.name, &u.name, &progress, &target])?;
+                }
+            }
+        }
+    }
+    out.flush()
+}
+
+// Laid out as in the tracker, with a row for each zone and a column for each usage
+pub fn export_grid<W: Write>(store: &ProgressStore, w: W) -> Result<()> {
+    let usages: Vec<_> = store
+        .abilities
+        .iter()
+        .flat_map(|a| a.usages.iter().map(move |u| (a, u)))
+        .collect();
+
+    let mut out = Writer::from_writer(w);
+    let mut abilities = vec![String::new(), String::new()];
+    let mut names = vec![COLUMNS[0].to_string(), COLUMNS[1].to_string()];
+    for (i, (a, u)) in usages.iter().enumerate() {
+        let first = i == 0 || usages[i - 1].0.id != a.id;
+        abilities.push(if first { a.name.clone() } else { String::new() });
+        names.push(u.name.clone());
+    }
+    out.write_record(&abilities)?;
+    out.write_record(&names)?;
+
+    for m in &store.maps {
+        for (i, z) in m.zones.iter().enumerate() {
+            let mut row = vec![
+                if i == 0 {
+                    m.name.clone()
+                } else {
+                    String::new()
+                },
+                z.name.clone(),
+            ];
+            for (a, u) in &usages {
+                row.push(match store.progress.get(&(m.id, z.id, a.id, u.id)) {
+                    Some(t) => format!("{}/{}", t.progress, t.target),
+                    None => "-".to_string(),
+                });
+            }
+            out.write_record(&row)?;
+        }
+    }
+    out.flush()
+}
+
+// Reads either layout, adding whatever is missing and never removing anything. Returns a line
+// describing each change made.
+pub fn import<R: Read>(store: &mut ProgressStore, r: R) -> Result<Vec<String>> {
+    let records = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(r)
+        .into_records()
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let rows = if let Some(columns) = records.first().and_then(long_columns) {
+        long_rows(&columns, &records[1..])?
+    } else if records.len() >= 2 && is_grid_header(&records[1]) {
+        grid_rows(&records[0], &records[1], &records[2..])?
+    } else {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "expected a header naming the map, zone, ability, usage, progress and target columns, \
+             or a grid as exported with --grid",
+        ));
+    };
+
+    let mut changes = Vec::new();
+    for row in rows {
+        apply(store, row, &mut changes)?;
+    }
+    Ok(changes)
+}
+
+fn long_columns(header: &StringRecord) -> Option<[usize; 6]> {
+    let mut columns = [0; 6];
+    for (column, name) in columns.iter_mut().zip(COLUMNS) {
+        *column = header.iter().position(|h| h.eq_ignore_ascii_case(name))?;
+    }
+    Some(columns)
+}
+
+fn long_rows(columns: &[usize; 6], records: &[StringRecord]) -> Result<Vec<Row>> {
+    let mut rows = Vec::new();
+    for record in records.iter().filter(|r| !is_blank(r)) {
+        let cell = |i: usize| record.get(columns[i]).unwrap_or_default().to_string();
+        let target = match (cell(4).as_str(), cell(5).as_str()) {
+            ("", "") => None,
+            (progress, target) => match (progress.parse(), target.parse()) {
+                (Ok(progress), Ok(target)) => Some((progress, target)),
+                _ => {
+                    return Err(invalid(
+                        line(record),
+                        format!("expected numbers, got '{}' and '{}'", progress, target),
+                    ))
+                }
+            },
+        };
+        rows.push(Row {
+            line: line(record),
+            names: [cell(0), cell(1), cell(2), cell(3)],
+            target,
+        });
+    }
+    Ok(rows)
+}
+
+fn is_grid_header(record: &StringRecord) -> bool {
+    let mut cells = record.iter();
+    cells
+        .next()
+        .is_some_and(|c| c.eq_ignore_ascii_case(COLUMNS[0]))
+        && cells
+            .next()
+            .is_some_and(|c| c.eq_ignore_ascii_case(COLUMNS[1]))
+}
+
+// Map and ability names are only given where they change, as in the tracker
+fn grid_rows(
+    abilities: &StringRecord,
+    usages: &StringRecord,
+    records: &[StringRecord],
+) -> Result<Vec<Row>> {
+    let mut columns = Vec::new();
+    let mut ability = "";
+    for (i, usage) in usages.iter().enumerate().skip(2) {
+        ability = match abilities.get(i) {
+            Some(a) if !a.is_empty() => a,
+            _ => ability,
+        };
+        if ability.is_empty() || usage.is_empty() {
+            return Err(invalid(
+                line(usages),
+                format!("column {} does not name an ability and usage", i + 1),
+            ));
+        }
+        columns.push((i, ability, usage));
+    }
+
+    let mut rows = Vec::new();
+    let mut map = "";
+    for record in records.iter().filter(|r| !is_blank(r)) {
+        map = match record.get(0) {
+            Some(m) if !m.is_empty() => m,
+            _ => map,
+        };
+        let zone = record.get(1).unwrap_or_default();
+        for (i, ability, usage) in &columns {
+            let target = match record.get(*i).unwrap_or_default() {
+                "" | "-" => None,
+                cell => match cell
+                    .split_once('/')
+                    .map(|(p, t)| (p.trim().parse(), t.trim().parse()))
+                {
+                    Some((Ok(progress), Ok(target))) => Some((progress, target)),
+                    _ => {
+                        return Err(invalid(
+                            line(record),
+                            format!("expected progress/target, got '{}'", cell),
+                        ))
+                    }
+                },
+            };
+            rows.push(Row {
+                line: line(record),
+                names: [map, zone, ability, usage].map(str::to_string),
+                target,
+            });
+        }
+    }
+    Ok(rows)
+}
+
+fn apply(store: &mut ProgressStore, row: Row, changes: &mut Vec<String>) -> Result<()> {
+    for name in &row.names {
+        check_name(name).map_err(|e| invalid(row.line, e))?;
+    }
+    let [map, zone, ability, usage] = &row.names;
+    let at = |e| invalid(row.line, e);
+
+    let mi = match position(store, &store.maps, map) {
+        Some(i) => i,
+        None => {
+            store.add_map(map.clone()).map_err(at)?;
+            changes.push(format!("+ map {}", map));
+            store.maps.len() - 1
+        }
+    };
+    let zi = match position(store, &store.maps[mi].zones, zone) {
+        Some(i) => i,
+        None => {
+            let map = store.maps[mi].name.clone();
+            store
+                .add_zone(&Selector::Name(map.clone()), zone.clone())
+                .map_err(at)?;
+            changes.push(format!("+ zone {} › {}", map, zone));
+            store.maps[mi].zones.len() - 1
+        }
+    };
+    let ai = match position(store, &store.abilities, ability) {
+        Some(i) => i,
+        None => {
+            store.add_ability(ability.clone()).map_err(at)?;
+            changes.push(format!("+ ability {}", ability));
+            store.abilities.len() - 1
+        }
+    };
+    let ui = match position(store, &store.abilities[ai].usages, usage) {
+        Some(i) => i,
+        None => {
+            let ability = store.abilities[ai].name.clone();
+            store
+                .add_usage(&Selector::Name(ability.clone()), usage.clone())
+                .map_err(at)?;
+            changes.push(format!("+ usage {} › {}", ability, usage));
+            store.abilities[ai].usages.len() - 1
+        }
+    };
+
+    let Some((progress, target)) = row.target else {
+        return Ok(());
+    };
+    let (m, a) = (&store.maps[mi], &store.abilities[ai]);
+    let (z, u) = (&m.zones[zi], &a.usages[ui]);
+    let key: LineupKey = (m.id, z.id, a.id, u.id);
+    let path = format!("{} › {} › {} › {}", m.name, z.name, a.name, u.name);
+
+    let old = store.progress.get(&key).cloned();
+    let mut new = old.clone().unwrap_or_default();
+    // Progress is assigned directly, as importing it is not practice
+    new.progress = progress;
+    new.set_target(target);
+    match old {
+        Some(old) if (old.progress, old.target) == (new.progress, new.target) => return Ok(()),
+        Some(old) => changes.push(format!(
+            "~ {}  {}/{} → {}/{}",
+            path, old.progress, old.target, new.progress, new.target
+        )),
+        None => changes.push(format!("+ {}  {}/{}", path, new.progress, new.target)),
+    }
+    store.set_target_of(key, new).map_err(at)
+}
+
+fn position<T: Nameable>(store: &ProgressStore, vs: &[T], name: &str) -> Option<usize> {
+    vs.iter().position(|v| store.same_name(v.name(), name))
+}
+
+fn is_blank(record: &StringRecord) -> bool {
+    record.iter().all(str::is_empty)
+}
+
+fn line(record: &StringRecord) -> u64 {
+    record.position().map_or(0, |p| p.line())
+}
+
+fn invalid(line: u64, msg: impl Display) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("line {}: {}", line, msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::test_store;
+
+    fn long(store: &ProgressStore) -> String {
+        let mut data = Vec::new();
+        export_long(store, &mut data).unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    #[test]
+    fn exports_can_be_imported() {
+        let original = test_store();
+        for grid in [false, true] {
+            let mut data = Vec::new();
+            match grid {
+                false => export_long(&original, &mut data).unwrap(),
+                true => export_grid(&original, &mut data).unwrap(),
+            }
+            let mut imported = ProgressStore::new("Progress".into());
+            let changes = import(&mut imported, data.as_slice()).unwrap();
+            assert_eq!(changes[..2], ["+ map Haven", "+ zone Haven › A Main"]);
+            assert_eq!(long(&imported), long(&original));
+        }
+    }
+
+    #[test]
+    fn imports_describe_their_changes() {
+        let mut store = test_store();
+        let data = "\
+Usage,Ability,Zone,Map,Target,Progress
+One-way,Smoke,A Main,Haven,2,0
+Default,Smoke,A Main,Haven,5,1
+Default,Smoke,Hookah,Bind,3,1
+";
+        assert_eq!(
+            import(&mut store, data.as_bytes()).unwrap(),
+            [
+                "~ Haven › A Main › Smoke › Default  1/2 → 1/5",
+                "+ map Bind",
+                "+ zone Bind › Hookah",
+                "~ Bind › Hookah › Smoke › Default  0/2 → 1/3",
+            ]
+        );
+        assert!(store.progress.values().all(|t| t.last_practised.is_none()));
+
+        let data = "Smoke\nmap,zone,\nHaven,A Main,3/4\n";
+        assert_eq!(
+            import(&mut store, data.as_bytes()).unwrap_err().to_string(),
+            "line 2: column 3 does not name an ability and usage"
+        );
+    }
+}
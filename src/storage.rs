@@ -0,0 +1,518 @@
+use crate::journal::Journal;
+use chrono::{Local, NaiveDateTime, TimeDelta};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use shellexpand::tilde;
+use std::cmp::Reverse;
+use std::env;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{BufWriter, Error, ErrorKind, Result, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const LEGACY_SAVE_LOC: &str = "~/.lineup-progress-rs.json";
+const FILE_VAR: &str = "LINEUP_TRACKER_FILE";
+const PROFILE_VAR: &str = "LINEUP_TRACKER_PROFILE";
+const DATA_DIR_NAME: &str = "lineup-tracker";
+const DEFAULT_SAVE_NAME: &str = "progress.json";
+const PROFILE_DIR_NAME: &str = "profiles";
+const DEFAULT_STORE_NAME: &str = "Progress";
+const BACKUP_TIME_FMT: &str = "%Y%m%d-%H%M%S-%3f";
+const BACKUP_EXT: &str = "bak";
+const RECOVERY_EXT: &str = "recovery";
+const JOURNAL_EXT: &str = "journal";
+const LOCK_EXT: &str = "lock";
+const WRITE_LOCK_EXT: &str = "write-lock";
+// How long to wait for whatever is writing the save file before giving up
+const WRITE_LOCK_WAIT: Duration = Duration::from_secs(5);
+pub const DEFAULT_BACKUPS: usize = 5;
+// Saves within this long of the last backup do not take another, so that autosaves and quick
+// runs of commands cannot rotate out the backups worth rolling back to
+const BACKUP_INTERVAL: TimeDelta = TimeDelta::hours(1);
+
+pub struct SaveLocation {
+    pub path: PathBuf,
+    pub profile: Option<String>,
+    pub backups: usize,
+}
+
+// Held by whichever tracker has the save file open, or by whatever is writing it
+pub struct Lock {
+    _file: File,
+}
+
+pub struct Backup {
+    pub path: PathBuf,
+    pub taken: NaiveDateTime,
+}
+
+impl SaveLocation {
+    pub fn resolve(file: Option<String>, profile: Option<String>) -> Result<Self> {
+        let var = |name| env::var(name).ok().filter(|v: &String| !v.is_empty());
+        Self::resolve_from((file, profile), (var(FILE_VAR), var(PROFILE_VAR)))
+    }
+
+    // Options take precedence over the environment, so only those from the same place conflict
+    fn resolve_from(
+        options: (Option<String>, Option<String>),
+        vars: (Option<String>, Option<String>),
+    ) -> Result<Self> {
+        let (file, profile) = match (options, vars) {
+            ((Some(_), Some(_)), _) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "a save file and a profile cannot both be specified",
+                ))
+            }
+            ((None, None), (Some(_), Some(_))) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{} and {} cannot both be set", FILE_VAR, PROFILE_VAR),
+                ))
+            }
+            ((None, None), vars) => vars,
+            (options, _) => options,
+        };
+
+        let path = match (file, &profile) {
+            (Some(file), _) => PathBuf::from(tilde(&file).as_ref()),
+            (None, Some(profile)) => Self::profile_path(profile)?,
+            (None, None) => Self::default_path(),
+        };
+
+        Ok(Self {
+            path,
+            profile,
+            backups: DEFAULT_BACKUPS,
+        })
+    }
+
+    pub fn store_name(&self) -> String {
+        self.profile
+            .clone()
+            .unwrap_or_else(|| DEFAULT_STORE_NAME.to_string())
+    }
+
+    pub fn profiles() -> Result<Vec<(String, PathBuf)>> {
+        let dir = Self::data_dir().join(PROFILE_DIR_NAME);
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut profiles = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                profiles.push((name.to_string(), path.clone()));
+            }
+        }
+        profiles.sort();
+        Ok(profiles)
+    }
+
+    pub fn ensure_parent(&self) -> Result<()> {
+        match self.path.parent() {
+            Some(dir) if dir != Path::new("") => fs::create_dir_all(dir),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn write<F>(&self, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut BufWriter<File>) -> Result<()>,
+    {
+        self.replace(write, false)
+    }
+
+    fn replace<F>(&self, write: F, always_backup: bool) -> Result<()>
+    where
+        F: FnOnce(&mut BufWriter<File>) -> Result<()>,
+    {
+        self.ensure_parent()?;
+
+        let tmp = self.sibling("tmp");
+        let res = Self::write_synced(&tmp, write)
+            .and_then(|_| self.backup(always_backup))
+            .and_then(|_| fs::rename(&tmp, &self.path));
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        res?;
+
+        self.sync_dir()
+    }
+
+    fn write_synced<F>(path: &Path, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut BufWriter<File>) -> Result<()>,
+    {
+        let mut w = BufWriter::new(File::create(path)?);
+        write(&mut w)?;
+        let f = w.into_inner().map_err(|e| e.into_error())?;
+        f.sync_all()
+    }
+
+    #[cfg(unix)]
+    fn sync_dir(&self) -> Result<()> {
+        match self.path.parent() {
+            Some(dir) if dir != Path::new("") => File::open(dir)?.sync_all(),
+            _ => File::open(".")?.sync_all(),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn sync_dir(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn backup(&self, always: bool) -> Result<()> {
+        if self.backups == 0 || !self.path.exists() {
+            return Ok(());
+        }
+
+        let now = Local::now();
+        let recent = self
+            .list_backups()?
+            .first()
+            .is_some_and(|b| now.naive_local() - b.taken < BACKUP_INTERVAL);
+        if recent && !always {
+            return Ok(());
+        }
+
+        let stamp = now.format(BACKUP_TIME_FMT).to_string();
+        fs::copy(
+            &self.path,
+            self.sibling(&format!("{}.{}", stamp, BACKUP_EXT)),
+        )?;
+
+        for old in self.list_backups()?.into_iter().skip(self.backups) {
+            fs::remove_file(old.path)?;
+        }
+        Ok(())
+    }
+
+    pub fn list_backups(&self) -> Result<Vec<Backup>> {
+        let dir = match self.path.parent() {
+            Some(dir) if dir != Path::new("") => dir,
+            _ => Path::new("."),
+        };
+        let prefix = match self.path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => format!("{}.", name),
+            None => return Ok(Vec::new()),
+        };
+        let suffix = format!(".{}", BACKUP_EXT);
+
+        let mut backups = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let stamp = name
+                .to_str()
+                .and_then(|n| n.strip_prefix(&prefix))
+                .and_then(|n| n.strip_suffix(&suffix));
+            if let Some(Ok(taken)) =
+                stamp.map(|s| NaiveDateTime::parse_from_str(s, BACKUP_TIME_FMT))
+            {
+                backups.push(Backup {
+                    path: entry.path(),
+                    taken,
+                });
+            }
+        }
+        backups.sort_by_key(|b| Reverse(b.taken));
+        Ok(backups)
+    }
+
+    pub fn restore(&self, backup: &Backup) -> Result<()> {
+        let data = fs::read(&backup.path)?;
+        self.replace(|w| w.write_all(&data), true)
+    }
+
+    // Returns None if another tracker holds the lock
+    pub fn lock(&self) -> Result<Option<Lock>> {
+        let file = self.lock_file(LOCK_EXT)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Lock { _file: file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(e),
+        }
+    }
+
+    // Held from reading the save file to writing it back, so that trackers and commands changing
+    // it at once cannot lose each other's changes
+    pub fn lock_for_writing(&self) -> Result<Lock> {
+        let file = self.lock_file(WRITE_LOCK_EXT)?;
+        let started = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Lock { _file: file }),
+                Err(TryLockError::WouldBlock) if started.elapsed() < WRITE_LOCK_WAIT => {
+                    thread::sleep(Duration::from_millis(20))
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(Error::new(
+                        ErrorKind::ResourceBusy,
+                        format!("{} is being written elsewhere", self.path.display()),
+                    ))
+                }
+                Err(TryLockError::Error(e)) => return Err(e),
+            }
+        }
+    }
+
+    fn lock_file(&self, ext: &str) -> Result<File> {
+        self.ensure_parent()?;
+        OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.sibling(ext))
+    }
+
+    // Raises the flag whenever the save file changes, until the watcher is dropped. The directory
+    // is watched as saves replace the file rather than writing to it.
+    pub fn watch(&self, changed: Arc<AtomicBool>) -> Result<RecommendedWatcher> {
+        let name = self.path.file_name().map(|n| n.to_os_string());
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else {
+                return;
+            };
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+            if event.paths.iter().any(|p| p.file_name() == name.as_deref()) {
+                changed.store(true, Ordering::Relaxed);
+            }
+        })
+        .map_err(Error::other)?;
+
+        let dir = match self.path.parent() {
+            Some(dir) if dir != Path::new("") => dir,
+            _ => Path::new("."),
+        };
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(Error::other)?;
+        Ok(watcher)
+    }
+
+    pub fn recovery_path(&self) -> PathBuf {
+        self.sibling(RECOVERY_EXT)
+    }
+
+    pub fn write_recovery(&self, data: &[u8]) -> Result<PathBuf> {
+        self.ensure_parent()?;
+        let path = self.recovery_path();
+        Self::write_synced(&path, |w| w.write_all(data))?;
+        Ok(path)
+    }
+
+    pub fn journal(&self) -> Journal {
+        Journal::new(self.sibling(JOURNAL_EXT))
+    }
+
+    pub fn quarantine(&self) -> Result<PathBuf> {
+        let stamp = Local::now().format(BACKUP_TIME_FMT).to_string();
+        let aside = self.sibling(&format!("{}.corrupt", stamp));
+        fs::rename(&self.path, &aside)?;
+        Ok(aside)
+    }
+
+    fn sibling(&self, ext: &str) -> PathBuf {
+        let mut name = self
+            .path
+            .file_name()
+            .map(|n| n.to_os_string())
+            .unwrap_or_default();
+        name.push(".");
+        name.push(ext);
+        self.path.with_file_name(name)
+    }
+
+    fn profile_path(profile: &str) -> Result<PathBuf> {
+        if profile.is_empty() || profile.starts_with('.') || profile.contains(['/', '\\']) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid profile name '{}'", profile),
+            ));
+        }
+        Ok(Self::data_dir()
+            .join(PROFILE_DIR_NAME)
+            .join(format!("{}.json", profile)))
+    }
+
+    fn default_path() -> PathBuf {
+        let path = Self::data_dir().join(DEFAULT_SAVE_NAME);
+        let legacy = PathBuf::from(tilde(LEGACY_SAVE_LOC).as_ref());
+        if !path.exists() && legacy.exists() {
+            return legacy;
+        }
+        path
+    }
+
+    fn data_dir() -> PathBuf {
+        let base = match env::var("XDG_DATA_HOME") {
+            Ok(dir) if Path::new(&dir).is_absolute() => PathBuf::from(dir),
+            _ => PathBuf::from(tilde("~/.local/share").as_ref()),
+        };
+        base.join(DATA_DIR_NAME)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    fn scratch(name: &str, backups: usize) -> SaveLocation {
+        let dir = env::temp_dir().join(format!("lineup-storage-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        SaveLocation {
+            path: dir.join("p.json"),
+            profile: None,
+            backups,
+        }
+    }
+
+    fn save(loc: &SaveLocation, data: &str) -> Result<()> {
+        loc.write(|w| w.write_all(data.as_bytes()))
+    }
+
+    fn fake_backup(loc: &SaveLocation, stamp: &str, data: &str) {
+        fs::write(loc.sibling(&format!("{}.{}", stamp, BACKUP_EXT)), data).unwrap();
+    }
+
+    fn contents(backups: &[Backup]) -> Vec<String> {
+        backups
+            .iter()
+            .map(|b| fs::read_to_string(&b.path).unwrap())
+            .collect()
+    }
+
+    fn resolve(
+        options: (Option<&str>, Option<&str>),
+        vars: (Option<&str>, Option<&str>),
+    ) -> Result<SaveLocation> {
+        let owned = |(f, p): (Option<&str>, Option<&str>)| (f.map(Into::into), p.map(Into::into));
+        SaveLocation::resolve_from(owned(options), owned(vars))
+    }
+
+    #[test]
+    fn options_override_the_environment() {
+        let loc = resolve((None, Some("work")), (Some("env.json"), None)).unwrap();
+        assert_eq!(loc.profile.as_deref(), Some("work"));
+        assert!(loc.path.ends_with("profiles/work.json"));
+
+        let loc = resolve((Some("a.json"), None), (None, Some("home"))).unwrap();
+        assert_eq!(loc.profile, None);
+        assert_eq!(loc.path, PathBuf::from("a.json"));
+
+        let loc = resolve((Some("a.json"), None), (Some("b.json"), Some("home"))).unwrap();
+        assert_eq!(loc.path, PathBuf::from("a.json"));
+
+        let loc = resolve((None, None), (None, Some("home"))).unwrap();
+        assert_eq!(loc.profile.as_deref(), Some("home"));
+        assert!(loc.path.ends_with("profiles/home.json"));
+
+        let loc = resolve((None, None), (Some("b.json"), None)).unwrap();
+        assert_eq!(loc.path, PathBuf::from("b.json"));
+    }
+
+    #[test]
+    fn files_and_profiles_conflict_from_the_same_place() {
+        assert_eq!(
+            resolve((Some("a.json"), Some("work")), (None, None))
+                .err()
+                .unwrap()
+                .to_string(),
+            "a save file and a profile cannot both be specified"
+        );
+        assert_eq!(
+            resolve((None, None), (Some("b.json"), Some("home")))
+                .err()
+                .unwrap()
+                .to_string(),
+            "LINEUP_TRACKER_FILE and LINEUP_TRACKER_PROFILE cannot both be set"
+        );
+        assert_eq!(
+            resolve((None, Some("../work")), (None, None))
+                .err()
+                .unwrap()
+                .to_string(),
+            "invalid profile name '../work'"
+        );
+    }
+
+    #[test]
+    fn failed_writes_leave_the_file_alone() {
+        let loc = scratch("failed", 5);
+        save(&loc, "old").unwrap();
+        let res = loc.write(|w| {
+            w.write_all(b"half")?;
+            Err(Error::other("full"))
+        });
+        assert_eq!(res.unwrap_err().to_string(), "full");
+        assert_eq!(fs::read_to_string(&loc.path).unwrap(), "old");
+        assert!(!loc.sibling("tmp").exists());
+        assert!(loc.list_backups().unwrap().is_empty());
+    }
+
+    #[test]
+    fn backups_are_pruned_and_restored() {
+        let loc = scratch("rotate", 2);
+        save(&loc, "current").unwrap();
+        assert!(loc.list_backups().unwrap().is_empty());
+        fake_backup(&loc, "20200101-000000-000", "oldest");
+        fake_backup(&loc, "20200102-000000-000", "older");
+        fake_backup(&loc, "20200103-000000-000", "old");
+
+        save(&loc, "new").unwrap();
+        let backups = loc.list_backups().unwrap();
+        assert_eq!(contents(&backups), ["current", "old"]);
+
+        // Saves soon after a backup do not take another
+        save(&loc, "newer").unwrap();
+        assert_eq!(contents(&loc.list_backups().unwrap()), ["current", "old"]);
+
+        // But restores always do, so that they can be undone. Backups are stamped to the
+        // millisecond, so this one must not be taken in the same one as the last.
+        thread::sleep(Duration::from_millis(2));
+        loc.restore(&backups[1]).unwrap();
+        assert_eq!(fs::read_to_string(&loc.path).unwrap(), "old");
+        assert_eq!(contents(&loc.list_backups().unwrap()), ["newer", "current"]);
+    }
+
+    #[test]
+    fn backups_are_found_by_their_stamps() {
+        let loc = scratch("stamps", 5);
+        fake_backup(&loc, "20240229-235959-123", "");
+        fake_backup(&loc, "20240301-000000-000", "");
+        fake_backup(&loc, "yesterday", "");
+        fake_backup(&loc, "20241301-000000-000", "");
+        fs::write(
+            loc.path.with_file_name("q.json.20240101-000000-000.bak"),
+            "",
+        )
+        .unwrap();
+        fs::write(loc.sibling("20240101-000000-000.corrupt"), "").unwrap();
+
+        let taken: Vec<_> = loc
+            .list_backups()
+            .unwrap()
+            .iter()
+            .map(|b| b.taken.format("%Y-%m-%d %H:%M:%S%.3f").to_string())
+            .collect();
+        assert_eq!(
+            taken,
+            ["2024-03-01 00:00:00.000", "2024-02-29 23:59:59.123"]
+        );
+    }
+}